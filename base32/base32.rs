@@ -0,0 +1,23 @@
+use common::Command;
+use base64::codec;
+
+static PROGRAM: &str = "base32";
+
+pub fn command() -> Command {
+    Command::new(PROGRAM,
+        &["[OPTION]... [FILE]..."],
+        "Base32 encode or decode the FILEs, or standard input, to standard output.",
+        "The FILEs are read one after another. With no FILE, or when FILE is -,\n\
+        read standard input.\n\n\
+        The data are encoded as described for the base32 alphabet in RFC 4648.\n\
+        When decoding, the input may contain newlines in addition to the bytes of\n\
+        the formal base32 alphabet.  Use --ignore-garbage to attempt to recover\n\
+        from any other non-alphabet bytes in the encoded stream.",
+        &base64::options())
+}
+
+pub fn uumain(args: Vec<String>) {
+    if let Some(m) = command().parse(&args[1..]) {
+        base64::run(PROGRAM, &m, &codec::BASE32);
+    }
+}
@@ -1,213 +1,234 @@
-extern crate getopts;
-use getopts::{optflag,getopts,OptGroup};
-use std::{io,os};
-
-fn print_usage(program: &String, opts: &[OptGroup]) {
-    let desc = format!(
-        "Usage:\t{} [OPTION]... [FILE]...",
-            program);
-
-    println!("{}", getopts::usage(desc.as_slice(), opts));
-}
-
-fn print_error(program: &String, err: &str) {
-    println!("{}: {}", program, err);
-    os::set_exit_status(1);
+use common::{Command, print_error, print_file_error, optflag, optopt, Matches, OptGroup};
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use codec::Encoding;
+
+pub mod codec;
+pub mod uu;
+mod fast;
+
+static PROGRAM: &str = "base64";
+
+pub fn command() -> Command {
+    Command::new(PROGRAM,
+        &["[OPTION]... [FILE]..."],
+        "Base64 encode or decode the FILEs, or standard input, to standard output.",
+        "The FILEs are read one after another. With no FILE, or when FILE is -,\n\
+        read standard input.",
+        &options())
 }
 
-fn read_stdin() -> String {
-    let mut s = String::from_str("");
-    for line in io::stdin().lines() {
-        s.push_str(line.unwrap().as_slice());
-    }
-    return s;
+/// The options understood by `run`.
+pub fn options() -> Vec<OptGroup> {
+    vec![
+        optflag("d", "decode", "decode data"),
+        optflag("i", "ignore-garbage", "when decoding, ignore non-alphabet characters"),
+        optopt("w", "wrap", "wrap encoded lines after COLS character (default 76).\n\
+                             Use 0 to disable line wrapping", "COLS"),
+        optopt("o", "output", "write to FILE, which is replaced only once all the input\n\
+                               has been converted", "FILE")
+    ]
 }
 
-
-// char as u8 -> binary string
-fn to_binary_string(b: u8, len: uint) -> String {
-    let mut s = String::from_str("");
-    let mut i = b;
-
-    for _ in range(0u, len) {
-        let cur = i%2;
-        i /= 2;
-
-        s = format!("{}{}", cur, s);
-    }
-    s
+// The named files read one after another, as `cat` would. A file that
+// cannot be opened or read is reported and skipped.
+struct Inputs<'a> {
+    program: &'a str,
+    names: &'a [String],
+    // Index of the next file to open
+    next: usize,
+    current: Option<Box<dyn Read>>,
+    failed: bool
 }
 
-fn encode_byte(b: u8) -> u8 {
-    if b <= 25 {
-        b + 65
-    } else if b <= 51 {
-        b + (97 - 26)
-    } else if b <= 61 {
-        b - 4
-    } else if b == 62 {
-        43
-    } else {
-        // if b == 63
-        47
+impl<'a> Read for Inputs<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let current = match self.current {
+                Some(ref mut r) => r,
+                None => {
+                    if self.next == self.names.len() {
+                        return Ok(0);
+                    }
+                    let name = &self.names[self.next];
+                    self.next += 1;
+                    match common::open_input(name) {
+                        Ok(r) => self.current = Some(r),
+                        Err(e) => {
+                            print_file_error(self.program, name, &e);
+                            self.failed = true;
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            match current.read(buf) {
+                Ok(0) => {},
+                Ok(n) => return Ok(n),
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    print_file_error(self.program, &self.names[self.next - 1], &e);
+                    self.failed = true;
+                }
+            }
+            self.current = None;
+        }
     }
 }
 
-enum InputErr { InvalidCharacter }
-fn decode_byte(b: u8) -> Result<u8, InputErr> {
-    if b >= 65 && b <= 90 {
-        Ok(b - 65)
-    } else if b >= 97 && b <= 122 {
-        Ok((b - 97) + 26)
-    } else if b >= 48 && b <= 57 {
-        Ok((b - 48) + 52)
-    } else if b == 43 {
-        Ok(62)
-    } else if b == 47 {
-        Ok(63)
-    } else {
-        Err(InvalidCharacter)
+// How many symbolic links are followed from the output name, as Linux does
+const MAX_LINKS: usize = 40;
+
+// The file `dest` names once symbolic links are followed, which need not
+// exist yet
+fn resolve_links(dest: &Path) -> io::Result<PathBuf> {
+    let mut path = dest.to_path_buf();
+    for _ in 0..MAX_LINKS {
+        match fs::symlink_metadata(&path) {
+            Ok(ref st) if st.file_type().is_symlink() => {
+                // A relative link is relative to the directory holding it
+                let target = fs::read_link(&path)?;
+                path = match path.parent() {
+                    Some(dir) => dir.join(target),
+                    None => target
+                };
+            },
+            _ => return Ok(path)
+        }
     }
+    Err(io::Error::other("Too many levels of symbolic links"))
 }
 
-fn binary_to_chars(binary_chars: Vec<u8>, len: uint, f: |u:u8| -> u8) -> String {
-    let mut result = String::from_str("");
-    let mut pow = 1;
-    let mut buf = 0;
-    for (i, digit) in binary_chars.iter().rev().enumerate() {
-        let addend = (digit - 48) * pow;
-        buf += addend;
-        pow *= 2;
-
-        if i%len == len - 1 {
-            let c = f(buf) as char;
-            result = format!("{}{}", c, result);
-
-            // Reset
-            pow = 1;
-            buf = 0;
+// Create a hidden file next to `dest` under a name no file had. Creating
+// it exclusively means another process cannot slip a file or a link in
+// under the name first.
+fn create_temp(dest: &Path) -> io::Result<(File, PathBuf)> {
+    let file_name = dest.file_name().unwrap_or_default().to_string_lossy();
+    loop {
+        let name = format!(".{}.{:08x}.tmp", file_name, rand::random::<u32>());
+        let path = dest.with_file_name(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((file, path)),
+            Err(ref e) if e.kind() == ErrorKind::AlreadyExists => {},
+            Err(e) => return Err(e)
         }
     }
-    result
 }
 
-fn encode_base64(original_str: String) -> String {
-    let bytes = original_str.clone().into_bytes();
-
-    // Create u8 vec -> binary string
-    let mut s = String::from_str("");
-
-    for b in bytes.iter() {
-        let cur = to_binary_string(*b, 8);
-        s = s + cur;
-    }
-
-    // Add extra 0's if not divisible by 6
-    let mut binary_chars = s.into_bytes();
-    for _ in range(0u, (6 - binary_chars.len()%6)%6) {
-        binary_chars.push(48);
-    }
-
-    let result = binary_to_chars(binary_chars, 6, encode_byte);
-
-    // Add padding
-    let padding = match original_str.len()%3 {
-        0 => "",
-        1 => "==",
-        _ => "="
+// Write through `f` to a new file next to `dest`, which replaces `dest`
+// only once `f` succeeds. Until then `dest` is left as it was, even if the
+// program is killed. When `dest` is a symbolic link the file it points to
+// is replaced, and an existing file keeps its permissions.
+fn write_atomically<F>(program: &str, dest: &str, mut f: F)
+        where F: FnMut(&mut dyn Write) -> bool {
+    let target = match resolve_links(Path::new(dest)) {
+        Ok(p) => p,
+        Err(e) => {
+            print_file_error(program, dest, &e);
+            return
+        }
     };
-    format!("{}{}", result, padding)
-}
-
-fn decode_base64(original_str: String) -> Result<String, InputErr> {
-    let bytes = original_str.clone().into_bytes();
-
-    // Create u8 vec -> binary string
-    let mut s = String::from_str("");
-
-    for b in bytes.iter() {
-        let val = match decode_byte(*b) {
-            Ok(x) => x,
-            Err(e) => return Err(e)
-        };
-
-        let cur = to_binary_string(val, 6);
-        s = s + cur;
+    let (mut tmp, tmp_path) = match create_temp(&target) {
+        Ok(t) => t,
+        Err(e) => {
+            print_file_error(program, dest, &e);
+            return
+        }
+    };
+    let copy_mode = match fs::metadata(&target) {
+        Ok(st) => tmp.set_permissions(st.permissions()),
+        Err(_) => Ok(())
+    };
+    if let Err(e) = copy_mode {
+        let _ = fs::remove_file(&tmp_path);
+        print_file_error(program, dest, &e);
+        return
     }
 
-    // Add extra 0's if not divisible by 8
-    let mut binary_chars = s.clone().into_bytes();
-    for _ in range(0u, (8 - binary_chars.len()%8)%8) {
-        binary_chars.push(48);
+    if !f(&mut tmp) {
+        let _ = fs::remove_file(&tmp_path);
+        return
     }
-
-    let result = binary_to_chars(binary_chars, 8, |u:u8| -> u8 { u });
-
-    Ok(result)
-}
-
-#[deriving(Show)]
-enum FileErr { FileNotFound }
-fn read_file(filename: &String) -> Result<String, FileErr> {
-    let path = Path::new(filename.as_slice());
-    let mut file = std::io::File::open(&path);
-    let temp = file.read_to_end();
-    match temp {
-        Ok(s) => {
-            let contents = std::str::from_utf8_owned(s);
-            Ok(contents.unwrap())
-        },
-        Err(_) => {
-            Err(FileNotFound)
-        }
+    if let Err(e) = tmp.sync_all().and_then(|_| fs::rename(&tmp_path, &target)) {
+        let _ = fs::remove_file(&tmp_path);
+        print_file_error(program, dest, &e);
     }
 }
 
-fn main() {
-    let args = os::args();
-    let program = args.get(0).clone();
-
-    let opts = [
-        optflag("d", "decode", "decode data"),
-        optflag("h", "help", "display this help and exit")
-    ];
-    let matches = match getopts(args.tail(), opts) {
-        Ok(m) => { m }
-        Err(f) => { fail!(f) }
+/// Encode or decode the files named by `matches` with `enc`, reporting
+/// errors as `program`. The options are those of `base64`.
+pub fn run(program: &str, matches: &Matches, enc: &Encoding) {
+    let mut config = codec::Config::new(enc);
+    config.ignore_garbage = matches.opt_present("i");
+    config.wrap = match matches.opt_str("w") {
+        Some(s) => match s.parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => {
+                let msg = format!("invalid wrap size: '{}'", s);
+                print_error(program, &msg);
+                return
+            }
+        },
+        None => codec::DEFAULT_WRAP
     };
 
-    let help = matches.opt_present("h");
-    let free = matches.free.clone();
-    let len = free.len();
-
-    if help {
-        print_usage(&program, opts);
-        return
-    } else if len > 1 {
-        print_error(&program, "extra operand");
-        return
+    let mut files = matches.free.clone();
+    if files.is_empty() {
+        files.push(String::from("-"));
     }
+    let mut inputs = Inputs {
+        program,
+        names: &files,
+        next: 0,
+        current: None,
+        failed: false
+    };
 
-    let s =
-        if len == 0 || (*free.get(0)) == String::from_str("-") {
-            read_stdin()
-        } else {
-            match read_file(free.get(0)) {
-                Ok(s) => s,
-                Err(_) => {
-                    print_error(&program, "No such file or directory");
-                    return
+    let output = matches.opt_str("o");
+    let decode = matches.opt_present("d");
+    let mut convert = |out: &mut dyn Write| -> bool {
+        let result =
+            if decode {
+                codec::decode(&mut inputs, out, &config)
+            } else {
+                codec::encode(&mut inputs, out, &config)
+            };
+        let result = result.and_then(|_| out.flush());
+
+        match result {
+            Ok(_) => !inputs.failed,
+            Err(ref e) if e.kind() == ErrorKind::InvalidData => {
+                let msg = format!("invalid input: {}", common::strerror(e));
+                print_error(program, &msg);
+                false
+            },
+            Err(e) => {
+                match output {
+                    Some(ref o) => print_file_error(program, o, &e),
+                    None => {
+                        let msg = format!("write error: {}", common::strerror(&e));
+                        print_error(program, &msg);
+                    }
                 }
-
+                false
             }
-        };
+        }
+    };
 
-    if matches.opt_present("d") {
-        match decode_base64(s) {
-            Ok(s) => println!("{}", s),
-            Err(_) => print_error(&program, "Input error")
+    match output {
+        Some(ref o) => write_atomically(program, o, convert),
+        None => {
+            convert(&mut io::stdout().lock());
         }
-    } else {
-        println!("{}", encode_base64(s));
+    }
+}
+
+pub fn uumain(args: Vec<String>) {
+    if let Some(m) = command().parse(&args[1..]) {
+        run(PROGRAM, &m, &codec::BASE64);
     }
 }
@@ -0,0 +1,922 @@
+//! Streaming encoders and decoders for the RFC 4648 encodings and Z85.
+//!
+//! An `Encoding` is an alphabet and the way input bits map onto it, and a
+//! `Config` adds how the command line tools lay out and read the text. The
+//! `Encoder` and `Decoder` work through any amount of input in pieces,
+//! holding at most one incomplete group between calls; `EncodeWriter` and
+//! `DecodeReader` wrap them around any writer or reader:
+//!
+//! ```ignore
+//! let config = Config::new(&codec::BASE64URL);
+//! let mut w = EncodeWriter::new(io::stdout(), &config);
+//! w.write_all(data)?;
+//! w.finish()?;
+//! ```
+
+use std::cmp;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use crate::fast;
+
+/// How bytes are turned into characters.
+pub enum Scheme {
+    /// Each character holds this many bits, most significant first
+    MsbFirst(usize),
+    /// Each character holds this many bits, least significant first
+    LsbFirst(usize),
+    /// Every 4 bytes are a big-endian number written as 5 base 85 digits
+    Base85,
+    /// Base85, but a final group of 1 to 3 bytes is written as 2 to 4
+    /// digits and a group of 4 zero bytes as 'z'
+    Ascii85
+}
+
+use self::Scheme::*;
+
+pub struct Encoding {
+    pub alphabet: &'static [u8],
+    pub scheme: Scheme,
+    /// Characters in a group: encoded output is padded, and decoded input
+    /// must be complete, to a whole number of groups
+    pub group: usize,
+    /// Whether incomplete groups are padded with '='
+    pub padded: bool,
+    /// Written before and after the encoded data
+    pub prefix: &'static [u8],
+    pub suffix: &'static [u8]
+}
+
+pub static BASE64: Encoding = Encoding {
+    alphabet: b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/",
+    scheme: MsbFirst(6),
+    group: 4,
+    padded: true,
+    prefix: b"",
+    suffix: b""
+};
+
+pub static BASE64URL: Encoding = Encoding {
+    alphabet: b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_",
+    scheme: MsbFirst(6),
+    group: 4,
+    padded: true,
+    prefix: b"",
+    suffix: b""
+};
+
+pub static BASE32: Encoding = Encoding {
+    alphabet: b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567",
+    scheme: MsbFirst(5),
+    group: 8,
+    padded: true,
+    prefix: b"",
+    suffix: b""
+};
+
+pub static BASE32HEX: Encoding = Encoding {
+    alphabet: b"0123456789ABCDEFGHIJKLMNOPQRSTUV",
+    scheme: MsbFirst(5),
+    group: 8,
+    padded: true,
+    prefix: b"",
+    suffix: b""
+};
+
+pub static BASE16: Encoding = Encoding {
+    alphabet: b"0123456789ABCDEF",
+    scheme: MsbFirst(4),
+    group: 2,
+    padded: false,
+    prefix: b"",
+    suffix: b""
+};
+
+pub static BASE2MSBF: Encoding = Encoding {
+    alphabet: b"01",
+    scheme: MsbFirst(1),
+    group: 8,
+    padded: false,
+    prefix: b"",
+    suffix: b""
+};
+
+pub static BASE2LSBF: Encoding = Encoding {
+    alphabet: b"01",
+    scheme: LsbFirst(1),
+    group: 8,
+    padded: false,
+    prefix: b"",
+    suffix: b""
+};
+
+pub static Z85: Encoding = Encoding {
+    alphabet: b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ.-:+=^!/*?&<>()[]{}@%$#",
+    scheme: Base85,
+    group: 5,
+    padded: false,
+    prefix: b"",
+    suffix: b""
+};
+
+// The digits of Ascii85 are the characters from '!' on
+static ASCII85_ALPHABET: &[u8] =
+    b"!\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstu";
+
+/// Ascii85 as btoa writes its data, without btoa's header and trailer.
+pub static ASCII85: Encoding = Encoding {
+    alphabet: ASCII85_ALPHABET,
+    scheme: Ascii85,
+    group: 5,
+    padded: false,
+    prefix: b"",
+    suffix: b""
+};
+
+/// Ascii85 as Adobe writes it in PostScript and PDF: between "<~" and "~>".
+pub static ADOBE85: Encoding = Encoding {
+    alphabet: ASCII85_ALPHABET,
+    scheme: Ascii85,
+    group: 5,
+    padded: false,
+    prefix: b"<~",
+    suffix: b"~>"
+};
+
+// Marks bytes outside the alphabet in the decoding table
+const INVALID: u8 = 0xff;
+
+// Output is collected and written this many bytes at a time
+const OUT_CHUNK: usize = 64 * 1024;
+
+/// Encoded lines are this long unless the caller says otherwise.
+pub const DEFAULT_WRAP: usize = 76;
+
+/// An encoding and how its text is laid out. Other alphabets or padding
+/// can be had with a new `Encoding`, e.g.
+/// `Encoding { padded: false, ..codec::BASE64URL }`.
+pub struct Config<'a> {
+    pub encoding: &'a Encoding,
+    /// Encoded line length, or 0 for a single unterminated line
+    pub wrap: usize,
+    /// When decoding, skip bytes outside the alphabet instead of failing
+    pub ignore_garbage: bool
+}
+
+impl<'a> Config<'a> {
+    /// `encoding` as the command line tools write it by default.
+    pub fn new(encoding: &'a Encoding) -> Config<'a> {
+        Config { encoding, wrap: DEFAULT_WRAP, ignore_garbage: false }
+    }
+}
+
+/// An error with kind `InvalidData` describing what is wrong with the data.
+pub fn invalid_input(detail: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, detail)
+}
+
+fn reverse_bits(b: u8) -> u8 {
+    let mut r = 0;
+    for i in 0..8 {
+        r |= ((b >> i) & 1) << (7 - i);
+    }
+    r
+}
+
+// `c` quoted, or its code if it is not printable
+fn describe(c: u8) -> String {
+    if (0x20..0x7f).contains(&c) {
+        format!("'{}'", c as char)
+    } else {
+        format!("byte 0x{:02x}", c)
+    }
+}
+
+// Whole groups of base64 and base64url have a faster path
+fn bulk_tables(enc: &Encoding) -> Option<fast::Tables> {
+    match enc.scheme {
+        MsbFirst(6) => Some(fast::Tables::new(enc.alphabet)),
+        _ => None
+    }
+}
+
+/// Encodes a stream of bytes in lines of `wrap` characters (0 for a single
+/// unterminated line).
+pub struct Encoder<'a> {
+    enc: &'a Encoding,
+    bulk: Option<fast::Tables>,
+    acc: u64,
+    nbits: usize,
+    // Characters of the current group
+    nchars: usize,
+    wrap: usize,
+    col: usize,
+    buf: Vec<u8>
+}
+
+impl<'a> Encoder<'a> {
+    pub fn new(enc: &'a Encoding, wrap: usize) -> Encoder<'a> {
+        let mut encoder = Encoder {
+            enc,
+            bulk: bulk_tables(enc),
+            acc: 0,
+            nbits: 0,
+            nchars: 0,
+            wrap,
+            col: 0,
+            buf: Vec::with_capacity(OUT_CHUNK)
+        };
+        for &c in enc.prefix {
+            encoder.push_text(c);
+        }
+        encoder
+    }
+
+    // A line is only ended once more output follows it, so that a final
+    // full line gets a single newline
+    fn push_text(&mut self, c: u8) {
+        if self.wrap > 0 && self.col == self.wrap {
+            self.buf.push(b'\n');
+            self.col = 0;
+        }
+        self.buf.push(c);
+        self.col += 1;
+    }
+
+    fn push_char(&mut self, c: u8) {
+        self.push_text(c);
+        self.nchars = (self.nchars + 1) % self.enc.group;
+    }
+
+    fn push_digits(&mut self, bits: usize) {
+        while self.nbits >= bits {
+            self.nbits -= bits;
+            let digit = (self.acc >> self.nbits) as usize & ((1 << bits) - 1);
+            let c = self.enc.alphabet[digit];
+            self.push_char(c);
+        }
+        self.acc &= (1 << self.nbits) - 1;
+    }
+
+    // The first `len` digits of the 4 bytes in `acc`
+    fn push_base85(&mut self, len: usize) {
+        let mut digits = [0u8; 5];
+        let mut n = self.acc;
+        for i in (0..5).rev() {
+            digits[i] = self.enc.alphabet[(n % 85) as usize];
+            n /= 85;
+        }
+        for &d in &digits[..len] {
+            self.push_char(d);
+        }
+        self.acc = 0;
+        self.nbits = 0;
+    }
+
+    fn flush(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(&self.buf)?;
+        self.buf.clear();
+        Ok(())
+    }
+
+    fn push_byte(&mut self, b: u8) {
+        match self.enc.scheme {
+            MsbFirst(bits) => {
+                self.acc = (self.acc << 8) | b as u64;
+                self.nbits += 8;
+                self.push_digits(bits);
+            },
+            LsbFirst(bits) => {
+                self.acc = (self.acc << 8) | reverse_bits(b) as u64;
+                self.nbits += 8;
+                self.push_digits(bits);
+            },
+            Base85 => {
+                self.acc = (self.acc << 8) | b as u64;
+                self.nbits += 8;
+                if self.nbits == 32 {
+                    self.push_base85(5);
+                }
+            },
+            Ascii85 => {
+                self.acc = (self.acc << 8) | b as u64;
+                self.nbits += 8;
+                if self.nbits == 32 && self.acc == 0 {
+                    self.push_text(b'z');
+                    self.nbits = 0;
+                } else if self.nbits == 32 {
+                    self.push_base85(5);
+                }
+            }
+        }
+    }
+
+    // How many whole groups of the next `len` bytes the bulk path can
+    // take: those that fit on the current line, once a full one is ended
+    fn bulk_groups(&mut self, len: usize) -> usize {
+        if self.bulk.is_none() || self.nbits != 0 || len < 3 {
+            return 0;
+        }
+        if self.wrap == 0 {
+            return len / 3;
+        }
+        if self.col == self.wrap {
+            self.buf.push(b'\n');
+            self.col = 0;
+        }
+        cmp::min(len / 3, (self.wrap - self.col) / 4)
+    }
+
+    pub fn update(&mut self, mut data: &[u8], out: &mut dyn Write) -> io::Result<()> {
+        while !data.is_empty() {
+            let groups = self.bulk_groups(data.len());
+            if groups > 0 {
+                let n = fast::encode(self.bulk.as_ref().unwrap(), &data[..groups * 3], &mut self.buf);
+                self.col += n / 3 * 4;
+                data = &data[n..];
+            } else {
+                self.push_byte(data[0]);
+                data = &data[1..];
+            }
+            if self.buf.len() >= OUT_CHUNK {
+                self.flush(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode the bits left over, pad the last group and end the last line.
+    pub fn finish(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        if self.nbits > 0 {
+            match self.enc.scheme {
+                MsbFirst(bits) | LsbFirst(bits) => {
+                    self.acc <<= bits - self.nbits;
+                    self.nbits = bits;
+                    self.push_digits(bits);
+                },
+                Base85 => {
+                    self.flush(out)?;
+                    return Err(invalid_input(
+                        String::from("length must be a multiple of 4 bytes")));
+                },
+                Ascii85 => {
+                    let len = self.nbits / 8 + 1;
+                    self.acc <<= 32 - self.nbits;
+                    self.push_base85(len);
+                }
+            }
+        }
+        if self.enc.padded {
+            while self.nchars != 0 {
+                self.push_char(b'=');
+            }
+        }
+        // The suffix is kept on one line
+        let suffix = self.enc.suffix;
+        if self.wrap > 0 && self.col + suffix.len() > self.wrap {
+            self.buf.push(b'\n');
+            self.col = 0;
+        }
+        for &c in suffix {
+            self.push_text(c);
+        }
+        if self.wrap > 0 && self.col > 0 {
+            self.buf.push(b'\n');
+        }
+        self.col = 0;
+        self.flush(out)
+    }
+}
+
+/// Decodes a stream of characters. A group of a padded encoding may end in
+/// '=' padding, and more groups may follow it. Line breaks are skipped, as is
+/// any white space in Ascii85, and with `ignore_garbage` so is anything
+/// else outside the alphabet. The encoding's prefix and suffix, if any,
+/// must surround the data.
+pub struct Decoder<'a> {
+    enc: &'a Encoding,
+    table: [u8; 256],
+    bulk: Option<fast::Tables>,
+    ignore_garbage: bool,
+    acc: u64,
+    nbits: usize,
+    // Characters of the current group, padding included
+    nchars: usize,
+    npad: usize,
+    // How much of the prefix and suffix has been read
+    prefix_seen: usize,
+    suffix_seen: usize,
+    // Offset in the input of the next character
+    pos: u64,
+    buf: Vec<u8>
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(enc: &'a Encoding, ignore_garbage: bool) -> Decoder<'a> {
+        let mut table = [INVALID; 256];
+        for (i, &c) in enc.alphabet.iter().enumerate() {
+            table[c as usize] = i as u8;
+        }
+        Decoder {
+            enc,
+            table,
+            bulk: bulk_tables(enc),
+            ignore_garbage,
+            acc: 0,
+            nbits: 0,
+            nchars: 0,
+            npad: 0,
+            prefix_seen: 0,
+            suffix_seen: 0,
+            pos: 0,
+            buf: Vec::with_capacity(OUT_CHUNK)
+        }
+    }
+
+    fn flush(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(&self.buf)?;
+        self.buf.clear();
+        Ok(())
+    }
+
+    // Write what was decoded so far, then fail with `detail`
+    fn fail(&mut self, out: &mut dyn Write, detail: String) -> io::Result<()> {
+        self.flush(out)?;
+        Err(invalid_input(detail))
+    }
+
+    // Padding may start once the group's characters hold at least a byte,
+    // and less than a character's worth of bits is left over
+    fn can_pad(&self) -> bool {
+        if self.npad > 0 {
+            return true;
+        }
+        match self.enc.scheme {
+            MsbFirst(bits) | LsbFirst(bits) if self.enc.padded => {
+                let total = self.nchars * bits;
+                total >= 8 && total % 8 < bits
+            },
+            _ => false
+        }
+    }
+
+    fn push_value(&mut self, val: u8) -> bool {
+        match self.enc.scheme {
+            MsbFirst(bits) | LsbFirst(bits) => {
+                self.acc = (self.acc << bits) | val as u64;
+                self.nbits += bits;
+                if self.nbits >= 8 {
+                    self.nbits -= 8;
+                    let b = (self.acc >> self.nbits) as u8;
+                    let b = match self.enc.scheme {
+                        LsbFirst(_) => reverse_bits(b),
+                        _ => b
+                    };
+                    self.buf.push(b);
+                    self.acc &= (1 << self.nbits) - 1;
+                }
+            },
+            Base85 | Ascii85 => {
+                self.acc = self.acc * 85 + val as u64;
+                if self.nchars == self.enc.group - 1 {
+                    if self.acc > 0xffffffff {
+                        return false;
+                    }
+                    for shift in [24, 16, 8, 0] {
+                        self.buf.push((self.acc >> shift) as u8);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    // The end of the data: decode an incomplete Ascii85 group as if it were
+    // padded with the highest digit
+    fn end_data(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        match self.enc.scheme {
+            Ascii85 if self.nchars >= 2 => {
+                let len = self.nchars - 1;
+                for _ in self.nchars..5 {
+                    self.acc = self.acc * 85 + 84;
+                }
+                if self.acc > 0xffffffff {
+                    let detail = format!("group ending at offset {} is out of range", self.pos);
+                    return self.fail(out, detail);
+                }
+                for shift in [24, 16, 8].iter().take(len) {
+                    self.buf.push((self.acc >> shift) as u8);
+                }
+                self.acc = 0;
+                self.nchars = 0;
+            },
+            _ => {}
+        }
+        if self.nchars != 0 {
+            let detail = format!("incomplete group at offset {}", self.pos);
+            return self.fail(out, detail);
+        }
+        Ok(())
+    }
+
+    // Whether `c` is skipped wherever it appears: a line break, or in
+    // Ascii85 any of PostScript's white space characters
+    fn is_space(&self, c: u8) -> bool {
+        match self.enc.scheme {
+            Ascii85 => matches!(c, b'\0' | b'\t' | b'\n' | b'\x0c' | b'\r' | b' '),
+            _ => c == b'\n'
+        }
+    }
+
+    // Whether the next character starts a group that the bulk path can take
+    fn bulk_ready(&self) -> bool {
+        self.bulk.is_some() && self.nchars == 0 && self.npad == 0 &&
+            self.prefix_seen == self.enc.prefix.len() && self.suffix_seen == 0
+    }
+
+    pub fn update(&mut self, mut data: &[u8], out: &mut dyn Write) -> io::Result<()> {
+        while !data.is_empty() {
+            if self.bulk_ready() {
+                let n = fast::decode(self.bulk.as_ref().unwrap(), data, &mut self.buf);
+                if n > 0 {
+                    self.pos += n as u64;
+                    data = &data[n..];
+                    if self.buf.len() >= OUT_CHUNK {
+                        self.flush(out)?;
+                    }
+                    continue;
+                }
+            }
+            self.update_one(data[0], out)?;
+            data = &data[1..];
+        }
+        Ok(())
+    }
+
+    fn update_one(&mut self, c: u8, out: &mut dyn Write) -> io::Result<()> {
+        let (prefix, suffix) = (self.enc.prefix, self.enc.suffix);
+        let pos = self.pos;
+        self.pos += 1;
+
+        // The frame around the data
+        if self.prefix_seen < prefix.len() && !self.is_space(c) {
+            if c != prefix[self.prefix_seen] {
+                let detail = format!("{} at offset {}, expected '{}'",
+                                     describe(c), pos, prefix[self.prefix_seen] as char);
+                return self.fail(out, detail);
+            }
+            self.prefix_seen += 1;
+            return Ok(());
+        }
+        if self.suffix_seen == 0 && !suffix.is_empty() && c == suffix[0] {
+            self.end_data(out)?;
+            self.suffix_seen = 1;
+            return Ok(());
+        }
+        if self.suffix_seen > 0 && !self.is_space(c) {
+            if self.suffix_seen == suffix.len() || c != suffix[self.suffix_seen] {
+                let detail = format!("{} at offset {} after the data", describe(c), pos);
+                return self.fail(out, detail);
+            }
+            self.suffix_seen += 1;
+            return Ok(());
+        }
+
+        // A group of 4 zero bytes
+        let val = self.table[c as usize];
+        match self.enc.scheme {
+            Ascii85 if c == b'z' && self.nchars == 0 => {
+                self.buf.extend_from_slice(&[0, 0, 0, 0]);
+                return Ok(());
+            },
+            _ => {}
+        }
+
+        // Padding ends a group: only more padding can follow it there
+        let pad = self.enc.padded && c == b'=';
+        if val != INVALID && self.npad == 0 {
+            if !self.push_value(val) {
+                let detail = format!("group ending at offset {} is out of range", pos);
+                return self.fail(out, detail);
+            }
+        } else if pad && self.can_pad() {
+            self.npad += 1;
+        } else if val == INVALID && (self.is_space(c) || (self.ignore_garbage && !pad)) {
+            return Ok(());
+        } else {
+            let detail = format!("{} at offset {}", describe(c), pos);
+            return self.fail(out, detail);
+        }
+
+        // Bits left over in a padded group are dropped
+        self.nchars += 1;
+        if self.nchars == self.enc.group {
+            self.acc = 0;
+            self.nbits = 0;
+            self.nchars = 0;
+            self.npad = 0;
+            if self.buf.len() >= OUT_CHUNK {
+                self.flush(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write what is left. An incomplete final group is invalid, though the
+    /// whole bytes in it have been written.
+    pub fn finish(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        let suffix = self.enc.suffix;
+        if self.suffix_seen == 0 {
+            self.end_data(out)?;
+        }
+        // Empty input needs no frame
+        if self.prefix_seen > 0 && self.suffix_seen < suffix.len() {
+            let detail = format!("missing '{}' at offset {}",
+                                 String::from_utf8_lossy(suffix), self.pos);
+            return self.fail(out, detail);
+        }
+        self.flush(out)
+    }
+}
+
+/// Encodes what is written to it, and writes the text to the writer it
+/// wraps. `finish` must be called to write the end of the text.
+pub struct EncodeWriter<'a, W> {
+    encoder: Encoder<'a>,
+    inner: W
+}
+
+impl<'a, W: Write> EncodeWriter<'a, W> {
+    pub fn new(inner: W, config: &Config<'a>) -> EncodeWriter<'a, W> {
+        EncodeWriter { encoder: Encoder::new(config.encoding, config.wrap), inner }
+    }
+
+    /// Write the last, padded group and line, and give back the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.encoder.finish(&mut self.inner)?;
+        Ok(self.inner)
+    }
+}
+
+impl<'a, W: Write> Write for EncodeWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder.update(buf, &mut self.inner)?;
+        Ok(buf.len())
+    }
+
+    // An incomplete group can only be written by `finish`
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush(&mut self.inner)?;
+        self.inner.flush()
+    }
+}
+
+/// Reads text from the reader it wraps and gives back the decoded bytes.
+/// Invalid input is an error of kind `InvalidData`, returned once the
+/// bytes decoded before it have been read.
+pub struct DecodeReader<'a, R> {
+    decoder: Decoder<'a>,
+    inner: R,
+    input: Vec<u8>,
+    // Decoded bytes not read yet start at `pos`
+    output: Vec<u8>,
+    pos: usize,
+    // Set at the end of the input, with the error that ended it, if any
+    done: bool,
+    error: Option<io::Error>
+}
+
+impl<'a, R: Read> DecodeReader<'a, R> {
+    pub fn new(inner: R, config: &Config<'a>) -> DecodeReader<'a, R> {
+        DecodeReader {
+            decoder: Decoder::new(config.encoding, config.ignore_garbage),
+            inner,
+            input: vec![0u8; common::BUFSIZE],
+            output: Vec::new(),
+            pos: 0,
+            done: false,
+            error: None
+        }
+    }
+
+    /// Give back the reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    // Decode the next piece of input into `output`
+    fn fill(&mut self) -> io::Result<()> {
+        let mut out = Vec::new();
+        let result = match self.inner.read(&mut self.input) {
+            Ok(0) => {
+                self.done = true;
+                self.decoder.finish(&mut out)
+            },
+            Ok(n) => match self.decoder.update(&self.input[..n], &mut out) {
+                Ok(_) => self.decoder.flush(&mut out),
+                Err(e) => Err(e)
+            },
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => Ok(()),
+            Err(e) => return Err(e)
+        };
+        if let Err(e) = result {
+            self.done = true;
+            self.error = Some(e);
+        }
+        self.output = out;
+        self.pos = 0;
+        Ok(())
+    }
+}
+
+impl<'a, R: Read> Read for DecodeReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.output.len() {
+            if self.done {
+                return match self.error.take() {
+                    Some(e) => Err(e),
+                    None => Ok(0)
+                };
+            }
+            self.fill()?;
+        }
+
+        let n = cmp::min(buf.len(), self.output.len() - self.pos);
+        buf[..n].copy_from_slice(&self.output[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// `data` encoded as one piece of text.
+pub fn encode_bytes(data: &[u8], config: &Config) -> io::Result<Vec<u8>> {
+    let mut w = EncodeWriter::new(Vec::new(), config);
+    w.write_all(data)?;
+    w.finish()
+}
+
+/// `text` decoded.
+pub fn decode_bytes(text: &[u8], config: &Config) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut decoder = Decoder::new(config.encoding, config.ignore_garbage);
+    decoder.update(text, &mut out)?;
+    decoder.finish(&mut out)?;
+    Ok(out)
+}
+
+/// Encode everything `reader` holds to `out`.
+pub fn encode(reader: &mut dyn Read, out: &mut dyn Write, config: &Config) -> io::Result<()> {
+    let mut encoder = Encoder::new(config.encoding, config.wrap);
+    common::for_each_chunk(reader, |chunk| encoder.update(chunk, out))?;
+    encoder.finish(out)
+}
+
+/// Decode everything `reader` holds to `out`.
+pub fn decode(reader: &mut dyn Read, out: &mut dyn Write, config: &Config) -> io::Result<()> {
+    let mut decoder = Decoder::new(config.encoding, config.ignore_garbage);
+    common::for_each_chunk(reader, |chunk| decoder.update(chunk, out))?;
+    decoder.finish(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+    use rand::seq::SliceRandom;
+    use crate::codec;
+    use crate::codec::{Config, Decoder, Encoder};
+    use crate::fast;
+
+    // The scalar path is checked against GNU basenc by the conformance
+    // tests; these check that the bulk path agrees with it, with each
+    // kernel the processor can run
+
+    // Encode and decode with the codec's scalar path only
+    fn scalar_encode(data: &[u8], wrap: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut encoder = Encoder::new(&codec::BASE64, wrap);
+        encoder.bulk = None;
+        encoder.update(data, &mut out).unwrap();
+        encoder.finish(&mut out).unwrap();
+        out
+    }
+
+    fn scalar_decode(text: &[u8], ignore_garbage: bool) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        let mut decoder = Decoder::new(&codec::BASE64, ignore_garbage);
+        decoder.bulk = None;
+        let result = decoder.update(text, &mut out).and_then(|_| decoder.finish(&mut out));
+        match result {
+            Ok(_) => Ok(out),
+            Err(e) => Err(e.to_string())
+        }
+    }
+
+    fn bulk_encode(kernel: fast::Kernel, data: &[u8], wrap: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut encoder = Encoder::new(&codec::BASE64, wrap);
+        encoder.bulk.as_mut().unwrap().kernel = kernel;
+        encoder.update(data, &mut out).unwrap();
+        encoder.finish(&mut out).unwrap();
+        out
+    }
+
+    fn bulk_decode(kernel: fast::Kernel, text: &[u8], ignore_garbage: bool)
+                   -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        let mut decoder = Decoder::new(&codec::BASE64, ignore_garbage);
+        decoder.bulk.as_mut().unwrap().kernel = kernel;
+        let result = decoder.update(text, &mut out).and_then(|_| decoder.finish(&mut out));
+        match result {
+            Ok(_) => Ok(out),
+            Err(e) => Err(e.to_string())
+        }
+    }
+
+    fn random_bytes<R: Rng>(rng: &mut R, max: usize) -> Vec<u8> {
+        let len = rng.gen_range(0..max);
+        (0..len).map(|_| rng.gen::<u8>()).collect()
+    }
+
+    #[test]
+    fn encode_matches_scalar() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..2000 {
+            let data = random_bytes(&mut rng, 300);
+            let wrap = rng.gen_range(0..80);
+            let expected = scalar_encode(&data, wrap);
+            for kernel in fast::kernels() {
+                assert_eq!(bulk_encode(kernel, &data, wrap), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn encode_in_pieces_matches_scalar() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..500 {
+            let data = random_bytes(&mut rng, 1000);
+            for kernel in fast::kernels() {
+                let mut out = Vec::new();
+                let mut encoder = Encoder::new(&codec::BASE64, 76);
+                encoder.bulk.as_mut().unwrap().kernel = kernel;
+                let mut rest = &data[..];
+                while !rest.is_empty() {
+                    let n = rng.gen_range(1..rest.len() + 1);
+                    encoder.update(&rest[..n], &mut out).unwrap();
+                    rest = &rest[n..];
+                }
+                encoder.finish(&mut out).unwrap();
+                assert_eq!(out, scalar_encode(&data, 76));
+            }
+        }
+    }
+
+    #[test]
+    fn decode_round_trips() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..2000 {
+            let data = random_bytes(&mut rng, 300);
+            let text = scalar_encode(&data, rng.gen_range(0..80));
+            for kernel in fast::kernels() {
+                assert_eq!(bulk_decode(kernel, &text, false), Ok(data.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn decode_damaged_matches_scalar() {
+        // Text with random bytes changed, some of them to characters the
+        // scalar path treats specially
+        let special = b"=\n!-_ ";
+        let mut rng = rand::thread_rng();
+        for _ in 0..2000 {
+            let data = random_bytes(&mut rng, 200);
+            let mut text = scalar_encode(&data, 76);
+            for _ in 0..rng.gen_range(0..4) {
+                if text.is_empty() {
+                    break;
+                }
+                let i = rng.gen_range(0..text.len());
+                text[i] = if rng.gen() {
+                    *special.choose(&mut rng).unwrap()
+                } else {
+                    rng.gen::<u8>()
+                };
+            }
+            let ignore = rng.gen();
+            let expected = scalar_decode(&text, ignore);
+            for kernel in fast::kernels() {
+                assert_eq!(bulk_decode(kernel, &text, ignore), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn ascii85_skips_white_space() {
+        let config = Config::new(&codec::ADOBE85);
+        let text = b"<~ 87cURD]i,\r\n\"Ebo7 ~>\r\n";
+        assert_eq!(codec::decode_bytes(text, &config).unwrap(), b"Hello World");
+        let config = Config::new(&codec::ASCII85);
+        let text = b"87c UR D]\ti,\x0c\"Ebo\r\n7";
+        assert_eq!(codec::decode_bytes(text, &config).unwrap(), b"Hello World");
+    }
+}
@@ -0,0 +1,330 @@
+//! Bulk encoding and decoding of whole base64 groups.
+//!
+//! The codec hands runs of complete groups to these loops. The table loops
+//! do a group per iteration with no branches on the data: encoding looks
+//! up two characters for each 12 bits, and decoding ORs together
+//! pre-shifted values from one table per character position, with a bit
+//! that is only set for bytes outside the alphabet.
+//!
+//! Where the processor has SSSE3 or AVX2 (see the `simd` crate), a vector
+//! loop goes first, 16 or 32 characters at a time, and the table loops
+//! finish what it leaves. Those loops look characters and values up in
+//! rows of 16 with byte shuffles, so they work for any alphabet. Anything
+//! else (partial groups, padding, line breaks, garbage) is left to the
+//! scalar codec.
+
+use simd::Feature;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+// Set in a decoded group when one of its characters is outside the alphabet
+const BAD: u32 = 1 << 24;
+
+// Marks characters outside the alphabet in the rows for the vector loops
+const NOT_VALUE: u8 = 0x80;
+
+// Moves the bytes of each group of 3 among the first 12 into the low 3
+// bytes of a 32-bit lane, last byte lowest; 0x80 clears a byte
+#[cfg(target_arch = "x86_64")]
+static ENC_SHUFFLE: [u8; 16] = [2, 1, 0, 0x80, 5, 4, 3, 0x80, 8, 7, 6, 0x80, 11, 10, 9, 0x80];
+
+// Moves the low 3 bytes of each 32-bit lane, highest first, into the first
+// 12 bytes
+#[cfg(target_arch = "x86_64")]
+static DEC_SHUFFLE: [u8; 16] = [2, 1, 0, 6, 5, 4, 10, 9, 8, 14, 13, 12, 0x80, 0x80, 0x80, 0x80];
+
+/// How whole groups are encoded and decoded.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Kernel {
+    /// The table loops alone
+    Table,
+    /// SSSE3, 12 bytes or 16 characters at a time, then the table loops
+    Ssse3,
+    /// AVX2, 24 bytes or 32 characters at a time, then the table loops
+    Avx2
+}
+
+use self::Kernel::*;
+
+// Each kernel with what it needs, the slowest first
+static KERNELS: &[(Kernel, &[Feature])] = &[
+    (Table, &[]),
+    (Ssse3, &[Feature::Ssse3]),
+    (Avx2, &[Feature::Avx2])
+];
+
+/// The kernels this processor can run, the fastest last, for the tests.
+#[cfg(test)]
+pub fn kernels() -> Vec<Kernel> {
+    simd::runnable(KERNELS)
+}
+
+pub struct Tables {
+    pub kernel: Kernel,
+    // The two characters for each 12 bit value, first one in the high byte
+    pairs: Vec<u16>,
+    // The value of each character shifted into place for each position of
+    // a group, or BAD
+    dec: [[u32; 256]; 4],
+    // For the vector loops: the alphabet, and the value of each ASCII
+    // character or NOT_VALUE, each in rows of 16
+    enc_rows: Vec<u8>,
+    dec_rows: Vec<u8>
+}
+
+impl Tables {
+    /// Tables for a 64 character alphabet, for the fastest kernel.
+    pub fn new(alphabet: &[u8]) -> Tables {
+        let mut pairs = Vec::with_capacity(4096);
+        for n in 0..4096 {
+            pairs.push(((alphabet[n >> 6] as u16) << 8) | alphabet[n & 63] as u16);
+        }
+
+        let mut dec = [[BAD; 256]; 4];
+        let mut dec_rows = vec![NOT_VALUE; 128];
+        for (val, &c) in alphabet.iter().enumerate() {
+            for (i, table) in dec.iter_mut().enumerate() {
+                table[c as usize] = (val as u32) << (18 - 6 * i);
+            }
+            dec_rows[c as usize] = val as u8;
+        }
+        Tables {
+            kernel: simd::fastest(KERNELS),
+            pairs,
+            dec,
+            enc_rows: Vec::from(alphabet),
+            dec_rows
+        }
+    }
+}
+
+/// Encode the whole groups of 3 bytes at the start of `input`, and return
+/// how many bytes that was.
+pub fn encode(tables: &Tables, input: &[u8], out: &mut Vec<u8>) -> usize {
+    let len = input.len() - input.len() % 3;
+    out.reserve(len / 3 * 4);
+    let done = encode_vector(tables, &input[..len], out);
+    done + encode_table(tables, &input[done..len], out)
+}
+
+fn encode_table(tables: &Tables, input: &[u8], out: &mut Vec<u8>) -> usize {
+    let pairs = &tables.pairs;
+    for group in input.chunks(3) {
+        let n = ((group[0] as usize) << 16) | ((group[1] as usize) << 8) | group[2] as usize;
+        let hi = pairs[n >> 12];
+        let lo = pairs[n & 0xfff];
+        out.push((hi >> 8) as u8);
+        out.push(hi as u8);
+        out.push((lo >> 8) as u8);
+        out.push(lo as u8);
+    }
+    input.len()
+}
+
+/// Decode the groups of 4 alphabet characters at the start of `input`, up
+/// to the first group holding anything else, and return how many
+/// characters that was.
+pub fn decode(tables: &Tables, input: &[u8], out: &mut Vec<u8>) -> usize {
+    let len = input.len() - input.len() % 4;
+    out.reserve(len / 4 * 3);
+    let done = decode_vector(tables, &input[..len], out);
+    done + decode_table(tables, &input[done..len], out)
+}
+
+fn decode_table(tables: &Tables, input: &[u8], out: &mut Vec<u8>) -> usize {
+    let mut done = 0;
+    for group in input.chunks(4) {
+        let n = tables.dec[0][group[0] as usize] | tables.dec[1][group[1] as usize] |
+                tables.dec[2][group[2] as usize] | tables.dec[3][group[3] as usize];
+        if n & BAD != 0 {
+            break;
+        }
+        out.push((n >> 16) as u8);
+        out.push((n >> 8) as u8);
+        out.push(n as u8);
+        done += 4;
+    }
+    done
+}
+
+#[cfg(target_arch = "x86_64")]
+fn encode_vector(tables: &Tables, input: &[u8], out: &mut Vec<u8>) -> usize {
+    match tables.kernel {
+        Table => 0,
+        Ssse3 => unsafe { encode_ssse3(tables, input, out) },
+        Avx2 => unsafe { encode_avx2(tables, input, out) }
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn encode_vector(_: &Tables, _: &[u8], _: &mut Vec<u8>) -> usize {
+    0
+}
+
+#[cfg(target_arch = "x86_64")]
+fn decode_vector(tables: &Tables, input: &[u8], out: &mut Vec<u8>) -> usize {
+    match tables.kernel {
+        Table => 0,
+        Ssse3 => unsafe { decode_ssse3(tables, input, out) },
+        Avx2 => unsafe { decode_avx2(tables, input, out) }
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn decode_vector(_: &Tables, _: &[u8], _: &mut Vec<u8>) -> usize {
+    0
+}
+
+// The rows of 16 bytes of a table
+#[cfg(target_arch = "x86_64")]
+fn rows(table: &[u8]) -> Vec<__m128i> {
+    table.chunks(16).map(simd::load).collect()
+}
+
+// Look up each byte of `index` in a table of rows of 16: every row is
+// searched with a shuffle, and only the one the high bits pick is kept.
+// Bytes past the end of the table look up 0.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "ssse3")]
+unsafe fn lookup(rows: &[__m128i], index: __m128i) -> __m128i {
+    let low = simd::splat(0x0f);
+    let row = _mm_and_si128(_mm_srli_epi16(index, 4), low);
+    let column = _mm_and_si128(index, low);
+    let mut found = _mm_setzero_si128();
+    for (i, &r) in rows.iter().enumerate() {
+        let hit = _mm_cmpeq_epi8(row, simd::splat(i as u8));
+        found = _mm_or_si128(found, _mm_and_si128(_mm_shuffle_epi8(r, column), hit));
+    }
+    found
+}
+
+// Each step reads 16 bytes and encodes the first 12. Needs SSSE3.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "ssse3")]
+unsafe fn encode_ssse3(tables: &Tables, input: &[u8], out: &mut Vec<u8>) -> usize {
+    let rows = rows(&tables.enc_rows);
+    let spread = simd::load(&ENC_SHUFFLE);
+    let mut done = 0;
+    while input.len() - done >= 16 {
+        let n = _mm_shuffle_epi8(simd::load(&input[done..]), spread);
+        // The four 6-bit values of each group, the first in the low byte
+        let values = _mm_or_si128(
+            _mm_or_si128(_mm_srli_epi32(n, 18),
+                         _mm_and_si128(_mm_srli_epi32(n, 4), _mm_set1_epi32(0xff00))),
+            _mm_or_si128(_mm_and_si128(_mm_slli_epi32(n, 10), _mm_set1_epi32(0xff0000)),
+                         _mm_slli_epi32(n, 24)));
+        let chars = lookup(&rows, _mm_and_si128(values, simd::splat(0x3f)));
+        out.extend_from_slice(&simd::to_bytes(chars));
+        done += 12;
+    }
+    done
+}
+
+// Each step decodes 16 characters into 12 bytes, until one of them is
+// outside the alphabet. Needs SSSE3.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "ssse3")]
+unsafe fn decode_ssse3(tables: &Tables, input: &[u8], out: &mut Vec<u8>) -> usize {
+    let rows = rows(&tables.dec_rows);
+    let gather = simd::load(&DEC_SHUFFLE);
+    let mut done = 0;
+    while input.len() - done >= 16 {
+        let chars = simd::load(&input[done..]);
+        // Characters outside the alphabet look up NOT_VALUE, or are not
+        // ASCII at all
+        let v = lookup(&rows, chars);
+        if _mm_movemask_epi8(_mm_or_si128(v, chars)) != 0 {
+            break;
+        }
+        let n = _mm_or_si128(
+            _mm_or_si128(_mm_slli_epi32(_mm_and_si128(v, _mm_set1_epi32(0x3f)), 18),
+                         _mm_slli_epi32(_mm_and_si128(v, _mm_set1_epi32(0x3f00)), 4)),
+            _mm_or_si128(_mm_srli_epi32(_mm_and_si128(v, _mm_set1_epi32(0x3f0000)), 10),
+                         _mm_srli_epi32(v, 24)));
+        let bytes = simd::to_bytes(_mm_shuffle_epi8(n, gather));
+        out.extend_from_slice(&bytes[..12]);
+        done += 16;
+    }
+    done
+}
+
+// The rows of a table, each in both halves of a 256-bit register
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn rows256(table: &[u8]) -> Vec<__m256i> {
+    table.chunks(16).map(|row| _mm256_broadcastsi128_si256(simd::load(row))).collect()
+}
+
+// `lookup` in 256-bit registers
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn lookup256(rows: &[__m256i], index: __m256i) -> __m256i {
+    let low = _mm256_set1_epi8(0x0f);
+    let row = _mm256_and_si256(_mm256_srli_epi16(index, 4), low);
+    let column = _mm256_and_si256(index, low);
+    let mut found = _mm256_setzero_si256();
+    for (i, &r) in rows.iter().enumerate() {
+        let hit = _mm256_cmpeq_epi8(row, _mm256_set1_epi8(i as i8));
+        found = _mm256_or_si256(found, _mm256_and_si256(_mm256_shuffle_epi8(r, column), hit));
+    }
+    found
+}
+
+// The bytes of a 256-bit register, first lane first
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn to_bytes256(v: __m256i) -> [u8; 32] {
+    let mut a = [0u8; 32];
+    _mm256_storeu_si256(a.as_mut_ptr() as *mut __m256i, v);
+    a
+}
+
+// The SSSE3 loop in 256-bit registers: each step reads 28 bytes and
+// encodes the first 24, the two halves of a register taking 12 each.
+// Needs AVX2.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn encode_avx2(tables: &Tables, input: &[u8], out: &mut Vec<u8>) -> usize {
+    let rows = rows256(&tables.enc_rows);
+    let spread = _mm256_broadcastsi128_si256(simd::load(&ENC_SHUFFLE));
+    let mut done = 0;
+    while input.len() - done >= 28 {
+        let bytes = _mm256_set_m128i(simd::load(&input[done + 12..]), simd::load(&input[done..]));
+        let n = _mm256_shuffle_epi8(bytes, spread);
+        let values = _mm256_or_si256(
+            _mm256_or_si256(_mm256_srli_epi32(n, 18),
+                            _mm256_and_si256(_mm256_srli_epi32(n, 4), _mm256_set1_epi32(0xff00))),
+            _mm256_or_si256(_mm256_and_si256(_mm256_slli_epi32(n, 10), _mm256_set1_epi32(0xff0000)),
+                            _mm256_slli_epi32(n, 24)));
+        let chars = lookup256(&rows, _mm256_and_si256(values, _mm256_set1_epi8(0x3f)));
+        out.extend_from_slice(&to_bytes256(chars));
+        done += 24;
+    }
+    done
+}
+
+// The SSSE3 loop in 256-bit registers, 32 characters a step; the values
+// are packed with multiply-adds, and the two halves' bytes brought
+// together with a permute. Needs AVX2.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn decode_avx2(tables: &Tables, input: &[u8], out: &mut Vec<u8>) -> usize {
+    let rows = rows256(&tables.dec_rows);
+    let gather = _mm256_broadcastsi128_si256(simd::load(&DEC_SHUFFLE));
+    let order = _mm256_setr_epi32(0, 1, 2, 4, 5, 6, 3, 7);
+    let mut done = 0;
+    while input.len() - done >= 32 {
+        let chars = _mm256_loadu_si256(input[done..done + 32].as_ptr() as *const __m256i);
+        let v = lookup256(&rows, chars);
+        if _mm256_movemask_epi8(_mm256_or_si256(v, chars)) != 0 {
+            break;
+        }
+        // Pairs of values into 12 bits, then pairs of those into 24
+        let pairs = _mm256_maddubs_epi16(v, _mm256_set1_epi32(0x01400140));
+        let n = _mm256_madd_epi16(pairs, _mm256_set1_epi32(0x00011000));
+        let packed = _mm256_permutevar8x32_epi32(_mm256_shuffle_epi8(n, gather), order);
+        out.extend_from_slice(&to_bytes256(packed)[..24]);
+        done += 32;
+    }
+    done
+}
@@ -0,0 +1,54 @@
+//! The line encoding of uuencode: each line starts with a character giving
+//! how many bytes it holds, followed by those bytes 3 to 4 characters, each
+//! character holding 6 bits as an offset from ' ' (with '`' for 0).
+
+use std::cmp;
+
+/// Bytes in a full line.
+pub const LINE_BYTES: usize = 45;
+
+fn uu_char(v: u8) -> u8 {
+    if v == 0 { b'`' } else { v + b' ' }
+}
+
+fn uu_value(c: u8) -> u8 {
+    c.wrapping_sub(b' ') & 63
+}
+
+/// Append the line holding `data`, at most `LINE_BYTES` long, to `out`.
+pub fn encode_line(data: &[u8], out: &mut Vec<u8>) {
+    out.push(uu_char(data.len() as u8));
+    for group in data.chunks(3) {
+        let b0 = group[0];
+        let b1 = if group.len() > 1 { group[1] } else { 0 };
+        let b2 = if group.len() > 2 { group[2] } else { 0 };
+        out.push(uu_char(b0 >> 2));
+        out.push(uu_char(((b0 << 4) | (b1 >> 4)) & 63));
+        out.push(uu_char(((b1 << 2) | (b2 >> 6)) & 63));
+        out.push(uu_char(b2 & 63));
+    }
+    out.push(b'\n');
+}
+
+/// Append the bytes held by `line`, without its newline, to `out`. Returns
+/// how many there were; 0 marks the end of the data. Characters missing at
+/// the end of a line, as when trailing spaces were stripped, count as ' '.
+pub fn decode_line(line: &[u8], out: &mut Vec<u8>) -> usize {
+    if line.is_empty() {
+        return 0;
+    }
+    let len = uu_value(line[0]) as usize;
+    let chars = &line[1..];
+    let value = |i: usize| if i < chars.len() { uu_value(chars[i]) } else { 0 };
+
+    let mut i = 0;
+    let mut written = 0;
+    while written < len {
+        let (c0, c1, c2, c3) = (value(i), value(i + 1), value(i + 2), value(i + 3));
+        let bytes = [(c0 << 2) | (c1 >> 4), (c1 << 4) | (c2 >> 2), (c2 << 6) | c3];
+        out.extend_from_slice(&bytes[..cmp::min(3, len - written)]);
+        written += cmp::min(3, len - written);
+        i += 4;
+    }
+    len
+}
@@ -1,75 +1,58 @@
-extern crate getopts;
-use getopts::{optopt,optflag,getopts,OptGroup};
-use std::os;
+use common::{Command, optopt, optflag};
 
-fn print_usage(program: &String, opts: &[OptGroup]) {
-    let desc = format!(
-        "Usage:\t{} NAME [SUFFIX]\n\
-            or:\t{} OPTION... NAME...",
-            program, program);
+static PROGRAM: &str = "basename";
 
-    println!("{}", getopts::usage(desc.as_slice(), opts));
+pub fn command() -> Command {
+    Command::new(PROGRAM,
+        &["NAME [SUFFIX]", "OPTION... NAME..."],
+        "Print NAME with any leading directory components removed.",
+        "If specified, also remove a trailing SUFFIX.",
+        &[
+            optopt("s", "suffix", "remove a trailing suffix", "SUFFIX"),
+            optflag("a", "multiple", "support multiple arguments and treat each as a NAME"),
+            optflag("z", "zero", "end each output line with NUL, not newline")
+        ])
 }
 
-fn print_error(msg: &str) {
-    println!("basename: {}", msg);
-    os::set_exit_status(1);
-}
-
-fn get_slash_indexes(s: &String) -> (uint, uint) {
-    let sliced = s.as_slice();
+fn get_slash_indexes(s: &str) -> (usize, usize) {
     let len = s.len();
 
     let mut trailing_slashes = 0;
     let mut is_last = true;
-    for (i, c) in sliced.chars().rev().enumerate() {
-        if c == '/' {
+    for (i, c) in s.bytes().rev().enumerate() {
+        if c == b'/' {
             if is_last {
                 trailing_slashes += 1;
             } else {
                 return (len - i, len - trailing_slashes);
             }
-        } else if c != '/' {
+        } else {
             is_last = false;
         }
     }
-    return (0, len - trailing_slashes);
+    (0, len - trailing_slashes)
 }
 
-fn print_basenames(v: &Vec<String>, suffix: &String, newline: &str) {
-    let suffix_len = suffix.len();
+fn print_basenames(v: &[String], suffix: &str, newline: &str) {
     for word in v.iter() {
         let (index1, index2) = get_slash_indexes(word);
-        let sliced = word.as_slice().slice(index1, index2);
+        let sliced = &word[index1..index2];
 
-        let match_index = sliced.len() - suffix_len;
-        let match_str = sliced.slice_from(match_index);
-        let result =
-            if match_str == suffix.as_slice() {
-                sliced.slice_to(match_index)
-            } else {
-                sliced
-            };
+        // A suffix as long as the whole name is not removed
+        let result = match sliced.strip_suffix(suffix) {
+            Some(rest) if !rest.is_empty() => rest,
+            _ => sliced
+        };
         print!("{}{}", result, newline);
     }
 }
 
-fn main() {
-    let args = os::args();
-    let program = args.get(0).clone();
-
-    let opts = [
-        optopt("s", "suffix", "remove a trailing suffix", "SUFFIX"),
-        optflag("a", "multiple", "support multiple arguments and treat each as a NAME"),
-        optflag("z", "zero", "end each output line with NUL, not newline"),
-        optflag("h", "help", "print this help menu")
-    ];
-    let matches = match getopts(args.tail(), opts) {
-        Ok(m) => { m }
-        Err(f) => { fail!(f) }
+pub fn uumain(args: Vec<String>) {
+    let matches = match command().parse(&args[1..]) {
+        Some(m) => m,
+        None => return
     };
 
-    let help = matches.opt_present("h");
     let suffix = matches.opt_str("s");
     let multiple = matches.opt_present("a") || matches.opt_present("s");
     let newline = if matches.opt_present("z") { "" } else { "\n" };
@@ -77,21 +60,20 @@ fn main() {
     let mut free = matches.free.clone();
     let len = free.len();
 
-    if help {
-        print_usage(&program, opts);
-    } else if len == 0 {
-        print_error("missing operand");
+    if len == 0 {
+        common::usage_error(PROGRAM, "missing operand");
     } else if len > 2 && !multiple {
-        print_error("too many operands");
+        let msg = format!("extra operand '{}'", free[2]);
+        common::usage_error(PROGRAM, &msg);
     } else {
         let suffix_str = match suffix {
             Some(a) => a,
             None =>
                 if len < 2 || multiple {
-                    String::from_str("")
+                    String::new()
                 } else {
                     // If two args, the second is the suffix
-                    let s = (*free.get(1)).clone();
+                    let s = free[1].clone();
                     free.pop();
                     s
                 }
@@ -0,0 +1,78 @@
+use common::{Command, usage_error, optflag};
+use base64::codec;
+use base64::codec::Encoding;
+
+static PROGRAM: &str = "basenc";
+
+// The encoding options, each named after its encoding
+static ENCODINGS: &[(&str, &str)] = &[
+    ("base64", "same as 'base64' program (RFC4648 section 4)"),
+    ("base64url", "file- and url-safe base64 (RFC4648 section 5)"),
+    ("base32", "same as 'base32' program (RFC4648 section 6)"),
+    ("base32hex", "extended hex alphabet base32 (RFC4648 section 7)"),
+    ("base16", "hex encoding (RFC4648 section 8)"),
+    ("base2msbf", "bit string with most significant bit (msb) first"),
+    ("base2lsbf", "bit string with least significant bit (lsb) first"),
+    ("z85", "ascii85-like encoding (ZeroMQ spec:32/Z85);\n\
+             when encoding, input length must be a multiple of 4;\n\
+             when decoding, input length must be a multiple of 5"),
+    ("ascii85", "Ascii85 as written by btoa (without its header and trailer)"),
+    ("adobe85", "Ascii85 between '<~' and '~>', as in PostScript and PDF")
+];
+
+fn find_encoding(name: &str) -> Option<&'static Encoding> {
+    match name {
+        "base64" => Some(&codec::BASE64),
+        "base64url" => Some(&codec::BASE64URL),
+        "base32" => Some(&codec::BASE32),
+        "base32hex" => Some(&codec::BASE32HEX),
+        "base16" => Some(&codec::BASE16),
+        "base2msbf" => Some(&codec::BASE2MSBF),
+        "base2lsbf" => Some(&codec::BASE2LSBF),
+        "z85" => Some(&codec::Z85),
+        "ascii85" => Some(&codec::ASCII85),
+        "adobe85" => Some(&codec::ADOBE85),
+        _ => None
+    }
+}
+
+pub fn command() -> Command {
+    let mut opts: Vec<_> = ENCODINGS.iter().map(|&(name, desc)| optflag("", name, desc)).collect();
+    opts.extend(base64::options());
+
+    Command::new(PROGRAM,
+        &["[OPTION]... [FILE]..."],
+        "basenc encode or decode the FILEs, or standard input, to standard output.",
+        "The FILEs are read one after another. With no FILE, or when FILE is -,\n\
+        read standard input.\n\n\
+        When decoding, the input may contain newlines in addition to the bytes of\n\
+        the formal alphabet.  Use --ignore-garbage to attempt to recover\n\
+        from any other non-alphabet bytes in the encoded stream.",
+        &opts)
+}
+
+// The encoding chosen last on the command line
+fn chosen_encoding(args: &[String]) -> Option<&'static Encoding> {
+    let mut enc = None;
+    for arg in args {
+        if arg == "--" {
+            break;
+        }
+        if let Some(e) = arg.strip_prefix("--").and_then(find_encoding) {
+            enc = Some(e);
+        }
+    }
+    enc
+}
+
+pub fn uumain(args: Vec<String>) {
+    let matches = match command().parse(&args[1..]) {
+        Some(m) => m,
+        None => return
+    };
+
+    match chosen_encoding(&args[1..]) {
+        Some(enc) => base64::run(PROGRAM, &matches, enc),
+        None => usage_error(PROGRAM, "missing encoding type")
+    }
+}
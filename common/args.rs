@@ -0,0 +1,145 @@
+//! Command line handling shared by every program. A `Command` describes
+//! what a program accepts; it parses the arguments, answers `--help` and
+//! `--version`, and is the source of the generated man pages and shell
+//! completions (see `docs`).
+
+use getopts::{HasArg, Occur, Options};
+use std::ops::Deref;
+
+/// Version of the suite, reported by every program's `--version`.
+pub static VERSION: &str = "0.1.0";
+
+/// An option a program accepts. A getopts `Options` cannot be looked into,
+/// so the options are kept in this form, from which `Command` builds the
+/// parser and `docs` the man pages and completions.
+#[derive(Clone)]
+pub struct OptGroup {
+    pub short_name: String,
+    pub long_name: String,
+    /// The name of the option's argument in help text, e.g. "FILE"
+    pub hint: String,
+    pub desc: String,
+    pub hasarg: HasArg
+}
+
+/// An option that takes no argument.
+pub fn optflag(short_name: &str, long_name: &str, desc: &str) -> OptGroup {
+    OptGroup {
+        short_name: String::from(short_name),
+        long_name: String::from(long_name),
+        hint: String::new(),
+        desc: String::from(desc),
+        hasarg: HasArg::No
+    }
+}
+
+/// An option that takes an argument, named `hint` in help text.
+pub fn optopt(short_name: &str, long_name: &str, desc: &str, hint: &str) -> OptGroup {
+    OptGroup {
+        short_name: String::from(short_name),
+        long_name: String::from(long_name),
+        hint: String::from(hint),
+        desc: String::from(desc),
+        hasarg: HasArg::Yes
+    }
+}
+
+/// The result of parsing a command line. Every option may be given more
+/// than once; as with GNU getopt, the last value given is the one that
+/// counts.
+pub struct Matches(getopts::Matches);
+
+impl Matches {
+    /// The argument given to the option `name` last, if it was given.
+    pub fn opt_str(&self, name: &str) -> Option<String> {
+        self.0.opt_strs(name).pop()
+    }
+}
+
+impl Deref for Matches {
+    type Target = getopts::Matches;
+
+    fn deref(&self) -> &getopts::Matches {
+        &self.0
+    }
+}
+
+pub struct Command {
+    pub name: &'static str,
+    /// Synopsis lines, without the program name: "[OPTION]... [FILE]..."
+    pub usage: Vec<&'static str>,
+    /// What the program does, in one line
+    pub about: &'static str,
+    /// More detail for --help and the man page; may be empty
+    pub description: &'static str,
+    /// The program's options, followed by --help and --version
+    pub opts: Vec<OptGroup>
+}
+
+impl Command {
+    pub fn new(name: &'static str, usage: &[&'static str], about: &'static str,
+               description: &'static str, opts: &[OptGroup]) -> Command {
+        let mut all_opts = Vec::from(opts);
+        all_opts.push(optflag("h", "help", "display this help and exit"));
+        all_opts.push(optflag("", "version", "output version information and exit"));
+
+        Command {
+            name,
+            usage: Vec::from(usage),
+            about,
+            description,
+            opts: all_opts
+        }
+    }
+
+    /// Parse `args`, which exclude the program name. Returns None when the
+    /// program has nothing left to do: help or version information was
+    /// printed, or a bad option was reported.
+    pub fn parse(&self, args: &[String]) -> Option<Matches> {
+        match self.options().parse(args.iter()) {
+            Ok(m) => {
+                if m.opt_present("help") {
+                    self.print_help();
+                    None
+                } else if m.opt_present("version") {
+                    self.print_version();
+                    None
+                } else {
+                    Some(Matches(m))
+                }
+            },
+            Err(f) => {
+                super::option_error(self.name, &f);
+                None
+            }
+        }
+    }
+
+    // The parser for the options
+    fn options(&self) -> Options {
+        let mut options = Options::new();
+        for opt in self.opts.iter() {
+            options.opt(&opt.short_name, &opt.long_name, &opt.desc, &opt.hint,
+                        opt.hasarg, Occur::Multi);
+        }
+        options
+    }
+
+    pub fn print_help(&self) {
+        let mut desc = String::new();
+        for (i, usage) in self.usage.iter().enumerate() {
+            let prefix = if i == 0 { "Usage:" } else { "\n   or:" };
+            desc.push_str(&format!("{}\t{} {}", prefix, self.name, usage));
+        }
+        desc.push_str(&format!("\n{}", self.about));
+        if !self.description.is_empty() {
+            desc.push_str(&format!("\n\n{}", self.description));
+        }
+
+        println!("{}", self.options().usage(&desc));
+    }
+
+    pub fn print_version(&self) {
+        println!("{} (rusty-coreutils) {}", self.name, VERSION);
+    }
+}
@@ -0,0 +1,251 @@
+//! Support code shared by every utility in the suite: opening inputs,
+//! reporting errors and handling the command line.
+
+use getopts::Fail;
+use std::env;
+use std::fs::File;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::atomic::{AtomicI32, Ordering};
+
+pub use args::{Command, Matches, OptGroup, optflag, optopt};
+
+pub mod args;
+pub mod docs;
+
+// Exit statuses, as in GNU coreutils. Usage errors exit with EXIT_FAILURE.
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+
+/// Size of the buffer used to read input a chunk at a time.
+pub const BUFSIZE: usize = 64 * 1024;
+
+// The status the process exits with once `main` returns
+static EXIT_STATUS: AtomicI32 = AtomicI32::new(EXIT_SUCCESS);
+
+/// Print `program: msg` on stderr and mark the run as failed.
+pub fn print_error(program: &str, msg: &str) {
+    let _ = writeln!(io::stderr(), "{}: {}", program, msg);
+    set_failure();
+}
+
+/// Print `program: filename: reason` on stderr, where the filename is
+/// quoted by `quote_name` and the reason is the operating system's
+/// description of `err`, and mark the run as failed.
+pub fn print_file_error(program: &str, filename: &str, err: &io::Error) {
+    let msg = format!("{}: {}", quote_name(filename), strerror(err));
+    print_error(program, &msg);
+}
+
+/// Quote a file name for a message the way GNU coreutils does: left as it
+/// is when a shell would read it literally, otherwise in single quotes, or
+/// in double quotes when that saves escaping a single quote. Characters
+/// that cannot be shown are written as `$'\n'` escapes.
+pub fn quote_name(name: &str) -> String {
+    quote(name, false)
+}
+
+/// Quote a file name as `quote_name` does, but in quotes even when a shell
+/// would read it literally, as GNU coreutils quotes names inside a message.
+pub fn quote_name_always(name: &str) -> String {
+    quote(name, true)
+}
+
+fn quote(name: &str, always: bool) -> String {
+    let utf8 = utf8_locale();
+    if name.is_empty() {
+        return String::from("''");
+    }
+    let special = always || name.starts_with('#') || name.starts_with('~') ||
+                  name.chars().any(|c| is_shell_special(c, utf8));
+    if !special {
+        return String::from(name);
+    }
+    if name.contains('\'') &&
+       name.chars().all(|c| is_printable(c, utf8) && c != '"' && c != '$' && c != '\\' && c != '`') {
+        return format!("\"{}\"", name);
+    }
+
+    let mut s = String::from("'");
+    let mut escaping = false;
+    for c in name.chars() {
+        if is_printable(c, utf8) {
+            if escaping {
+                s.push_str("''");
+                escaping = false;
+            }
+            if c == '\'' {
+                s.push_str("'\\''");
+            } else {
+                s.push(c);
+            }
+            continue;
+        }
+
+        if !escaping {
+            s.push_str("'$'");
+            escaping = true;
+        }
+        let escape = match c {
+            '\x07' => Some('a'), '\x08' => Some('b'), '\x0c' => Some('f'), '\n' => Some('n'),
+            '\r' => Some('r'), '\t' => Some('t'), '\x0b' => Some('v'),
+            _ => None
+        };
+        match escape {
+            Some(e) => {
+                s.push('\\');
+                s.push(e);
+            },
+            None => {
+                let mut utf8 = [0u8; 4];
+                for b in c.encode_utf8(&mut utf8).bytes() {
+                    s.push_str(&format!("\\{:03o}", b));
+                }
+            }
+        }
+    }
+    s.push('\'');
+    s
+}
+
+// Whether `c` can be shown as it is. Outside ASCII that depends on the
+// locale: in UTF-8 the name is printed as text, otherwise byte by byte.
+fn is_printable(c: char, utf8: bool) -> bool {
+    if c.is_ascii() { c >= ' ' && c != '\x7f' } else { utf8 }
+}
+
+// Whether a shell would take `c` as something other than a plain character
+fn is_shell_special(c: char, utf8: bool) -> bool {
+    match c {
+        ' ' | '!' | '"' | '$' | '&' | '\'' | '(' | ')' | '*' | ':' | ';' | '<' | '='
+            | '>' | '?' | '[' | '\\' | '^' | '`' | '|' => true,
+        _ => !is_printable(c, utf8)
+    }
+}
+
+/// Report a mistake in the command line, followed by a pointer to --help.
+pub fn usage_error(program: &str, msg: &str) {
+    print_error(program, msg);
+    let _ = writeln!(io::stderr(), "Try '{} --help' for more information.", program);
+}
+
+/// Report an option that getopts rejected, in the words of GNU getopt.
+pub fn option_error(program: &str, fail: &Fail) {
+    let msg = match *fail {
+        Fail::ArgumentMissing(ref opt) => format_option(opt, "option requires an argument"),
+        Fail::UnrecognizedOption(ref opt) =>
+            if opt.len() == 1 {
+                format!("invalid option -- '{}'", opt)
+            } else {
+                format!("unrecognized option '--{}'", opt)
+            },
+        // Every option is registered as repeatable
+        Fail::OptionDuplicated(_) => unreachable!(),
+        Fail::OptionMissing(ref opt) => format_option(opt, "missing required option"),
+        Fail::UnexpectedArgument(ref opt) => format!("option '--{}' doesn't allow an argument", opt)
+    };
+    usage_error(program, &msg);
+}
+
+fn format_option(opt: &str, msg: &str) -> String {
+    if opt.len() == 1 {
+        format!("{} -- '{}'", msg, opt)
+    } else {
+        format!("{} '--{}'", msg, opt)
+    }
+}
+
+/// The text of the C library's strerror() for `err`, e.g. "Permission denied".
+/// Errors made by the programs themselves give their own description.
+pub fn strerror(err: &io::Error) -> String {
+    let msg = err.to_string();
+    // The standard library adds the error number to the C library's text
+    match (err.raw_os_error(), msg.rfind(" (os error ")) {
+        (Some(_), Some(i)) => msg[..i].to_string(),
+        _ => msg
+    }
+}
+
+/// Make the process exit with `EXIT_FAILURE` once `main` returns.
+pub fn set_failure() {
+    set_exit_status(EXIT_FAILURE);
+}
+
+/// Make the process exit with `status` once `main` returns.
+pub fn set_exit_status(status: i32) {
+    EXIT_STATUS.store(status, Ordering::SeqCst);
+}
+
+/// The status set by `set_exit_status`, or `EXIT_SUCCESS`.
+pub fn exit_status() -> i32 {
+    EXIT_STATUS.load(Ordering::SeqCst)
+}
+
+/// Whether the character set of the locale, chosen as the C library does
+/// from LC_ALL, LC_CTYPE and LANG, is UTF-8. In any other locale, and in
+/// the C locale, text is taken a byte at a time.
+pub fn utf8_locale() -> bool {
+    for var in ["LC_ALL", "LC_CTYPE", "LANG"] {
+        match env::var_os(var) {
+            Some(ref value) if !value.is_empty() => {
+                let value = value.to_string_lossy().to_ascii_lowercase();
+                return value.contains("utf-8") || value.contains("utf8");
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Open `filename` for reading. The name `-` means standard input.
+pub fn open_input(filename: &str) -> io::Result<Box<dyn Read>> {
+    if filename == "-" {
+        Ok(Box::new(io::stdin()))
+    } else {
+        let file = File::open(filename)?;
+        Ok(Box::new(file))
+    }
+}
+
+/// A writer that remembers whether writing to it failed, so that the
+/// error from a copy can be told apart from a failure to read.
+pub struct Output<W: Write> {
+    inner: W,
+    /// Whether a write or flush returned an error
+    pub failed: bool
+}
+
+impl<W: Write> Output<W> {
+    pub fn new(inner: W) -> Output<W> {
+        Output { inner, failed: false }
+    }
+}
+
+impl<W: Write> Write for Output<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.inner.write(buf);
+        self.failed |= result.is_err();
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = self.inner.flush();
+        self.failed |= result.is_err();
+        result
+    }
+}
+
+/// Call `f` on each chunk read from `reader` until end of input, using a
+/// buffer of `BUFSIZE` bytes. Stops at the first error from either side.
+pub fn for_each_chunk<F>(reader: &mut dyn Read, mut f: F) -> io::Result<()>
+        where F: FnMut(&[u8]) -> io::Result<()> {
+    let mut buf = vec![0u8; BUFSIZE];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => f(&buf[..n])?,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e)
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Man pages and shell completions generated from a program's `Command`.
+
+use getopts::HasArg;
+use crate::args::{Command, OptGroup, VERSION};
+
+pub static SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+// Text with the characters roff treats specially escaped
+fn roff_escape(s: &str) -> String {
+    let mut out = String::new();
+    for line in s.split('\n') {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        if line.starts_with('.') || line.starts_with('\'') {
+            out.push_str("\\&");
+        }
+        out.push_str(&line.replace('\\', "\\\\").replace('-', "\\-"));
+    }
+    out
+}
+
+// "-s, --suffix=SUFFIX" in the given markup for option names and arguments
+fn option_names<N, A>(opt: &OptGroup, name: N, arg: A) -> String
+        where N: Fn(&str) -> String, A: Fn(&str) -> String {
+    let mut names = Vec::new();
+    if !opt.short_name.is_empty() {
+        names.push(name(&format!("-{}", opt.short_name)));
+    }
+    if !opt.long_name.is_empty() {
+        names.push(name(&format!("--{}", opt.long_name)));
+    }
+
+    let mut s = names.join(", ");
+    match opt.hasarg {
+        HasArg::Yes => {
+            let sep = if !opt.long_name.is_empty() { "=" } else { " " };
+            s.push_str(sep);
+            s.push_str(&arg(&opt.hint));
+        },
+        HasArg::Maybe => {
+            s.push_str(&format!("[={}]", arg(&opt.hint)));
+        },
+        HasArg::No => {}
+    }
+    s
+}
+
+/// A man page for `cmd`, in roff.
+pub fn man_page(cmd: &Command) -> String {
+    let mut out = String::new();
+    out.push_str(&format!(".TH {} 1 \"\" \"rusty-coreutils {}\" \"User Commands\"\n",
+                          cmd.name.to_ascii_uppercase(), VERSION));
+
+    out.push_str(".SH NAME\n");
+    out.push_str(&format!("{} \\- {}\n", cmd.name, roff_escape(cmd.about)));
+
+    out.push_str(".SH SYNOPSIS\n");
+    for (i, usage) in cmd.usage.iter().enumerate() {
+        if i > 0 {
+            out.push_str(".br\n");
+        }
+        out.push_str(&format!(".B {}\n{}\n", cmd.name, roff_escape(usage)));
+    }
+
+    out.push_str(".SH DESCRIPTION\n");
+    out.push_str(&format!("{}\n", roff_escape(cmd.about)));
+    if !cmd.description.is_empty() {
+        out.push_str(&format!(".PP\n{}\n", roff_escape(cmd.description)));
+    }
+
+    for opt in cmd.opts.iter() {
+        let names = option_names(opt,
+                                 |n| format!("\\fB{}\\fR", roff_escape(n)),
+                                 |a| format!("\\fI{}\\fR", roff_escape(a)));
+        out.push_str(&format!(".TP\n{}\n{}\n", names, roff_escape(&opt.desc)));
+    }
+    out
+}
+
+// `s` quoted for a POSIX shell
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace("'", "'\\''"))
+}
+
+fn bash_completion(cmd: &Command) -> String {
+    let mut words = Vec::new();
+    for opt in cmd.opts.iter() {
+        if !opt.short_name.is_empty() {
+            words.push(format!("-{}", opt.short_name));
+        }
+        if !opt.long_name.is_empty() {
+            words.push(format!("--{}", opt.long_name));
+        }
+    }
+
+    let func = format!("_rusty_coreutils_{}", cmd.name);
+    let mut out = String::new();
+    out.push_str(&func);
+    out.push_str("() {\n");
+    out.push_str("    local cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    out.push_str("    if [[ $cur == -* ]]; then\n");
+    out.push_str(&format!("        COMPREPLY=($(compgen -W {} -- \"$cur\"))\n",
+                          shell_quote(&words.join(" "))));
+    out.push_str("    else\n");
+    out.push_str("        COMPREPLY=($(compgen -f -- \"$cur\"))\n");
+    out.push_str("    fi\n");
+    out.push_str("}\n");
+    out.push_str(&format!("complete -F {} {}\n", func, cmd.name));
+    out
+}
+
+fn zsh_completion(cmd: &Command) -> String {
+    let mut out = format!("#compdef {}\n\n_arguments -s \\\n", cmd.name);
+    for opt in cmd.opts.iter() {
+        let desc = opt.desc.replace("[", "\\[").replace("]", "\\]");
+        let takes_arg = opt.hasarg != HasArg::No;
+        let mut names = Vec::new();
+        if !opt.short_name.is_empty() {
+            names.push(format!("-{}", opt.short_name));
+        }
+        if !opt.long_name.is_empty() {
+            let eq = if takes_arg { "=" } else { "" };
+            names.push(format!("--{}{}", opt.long_name, eq));
+        }
+        for name in names.iter() {
+            let mut spec = format!("{}[{}]", name, desc);
+            if takes_arg {
+                spec.push_str(&format!(":{}:", opt.hint.to_ascii_lowercase()));
+            }
+            out.push_str(&format!("    {} \\\n", shell_quote(&spec)));
+        }
+    }
+    out.push_str("    '*:file:_files'\n");
+    out
+}
+
+fn fish_completion(cmd: &Command) -> String {
+    let mut out = String::new();
+    for opt in cmd.opts.iter() {
+        let mut line = format!("complete -c {}", cmd.name);
+        if !opt.short_name.is_empty() {
+            line.push_str(&format!(" -s {}", opt.short_name));
+        }
+        if !opt.long_name.is_empty() {
+            line.push_str(&format!(" -l {}", opt.long_name));
+        }
+        match opt.hasarg {
+            HasArg::No => {},
+            _ => line.push_str(" -r")
+        }
+        line.push_str(&format!(" -d {}\n", shell_quote(&opt.desc)));
+        out.push_str(&line);
+    }
+    out
+}
+
+/// A completion script for `cmd` in the given shell, which is one of
+/// `SHELLS`.
+pub fn completion(cmd: &Command, shell: &str) -> Option<String> {
+    match shell {
+        "bash" => Some(bash_completion(cmd)),
+        "zsh" => Some(zsh_completion(cmd)),
+        "fish" => Some(fish_completion(cmd)),
+        _ => None
+    }
+}
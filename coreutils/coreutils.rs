@@ -0,0 +1,195 @@
+use common::{Command, print_error, usage_error};
+use common::docs;
+use std::env;
+use std::fs;
+use std::os::unix;
+use std::path::{Path, PathBuf};
+use std::process;
+
+static PROGRAM: &str = "coreutils";
+
+// Every applet in the suite; only those enabled as features are built in
+static ALL_APPLETS: &[&str] = &[
+    "base32", "base64", "basename", "basenc", "comm", "dirname", "seq",
+    "sleep", "tee", "uudecode", "uuencode", "wc", "yes"
+];
+
+// A program's entry point and the description of its command line
+struct Applet {
+    main: fn(Vec<String>),
+    command: fn() -> Command
+}
+
+fn find_applet(name: &str) -> Option<Applet> {
+    match name {
+        #[cfg(feature = "base32")]
+        "base32" => Some(Applet { main: base32::uumain, command: base32::command }),
+        #[cfg(feature = "base64")]
+        "base64" => Some(Applet { main: base64::uumain, command: base64::command }),
+        #[cfg(feature = "basename")]
+        "basename" => Some(Applet { main: basename::uumain, command: basename::command }),
+        #[cfg(feature = "basenc")]
+        "basenc" => Some(Applet { main: basenc::uumain, command: basenc::command }),
+        #[cfg(feature = "comm")]
+        "comm" => Some(Applet { main: comm::uumain, command: comm::command }),
+        #[cfg(feature = "dirname")]
+        "dirname" => Some(Applet { main: dirname::uumain, command: dirname::command }),
+        #[cfg(feature = "seq")]
+        "seq" => Some(Applet { main: seq::uumain, command: seq::command }),
+        #[cfg(feature = "sleep")]
+        "sleep" => Some(Applet { main: sleep::uumain, command: sleep::command }),
+        #[cfg(feature = "tee")]
+        "tee" => Some(Applet { main: tee::uumain, command: tee::command }),
+        #[cfg(feature = "uudecode")]
+        "uudecode" => Some(Applet { main: uudecode::uumain, command: uudecode::command }),
+        #[cfg(feature = "uuencode")]
+        "uuencode" => Some(Applet { main: uuencode::uumain, command: uuencode::command }),
+        #[cfg(feature = "wc")]
+        "wc" => Some(Applet { main: wc::uumain, command: wc::command }),
+        #[cfg(feature = "yes")]
+        "yes" => Some(Applet { main: yes::uumain, command: yes::command }),
+        _ => None
+    }
+}
+
+fn applets() -> Vec<&'static str> {
+    ALL_APPLETS.iter().copied().filter(|s| find_applet(s).is_some()).collect()
+}
+
+fn print_usage() {
+    println!("Usage:\t{} APPLET [ARGUMENT]...\n\
+             \tor:\tAPPLET [ARGUMENT]...   (via a link named APPLET)\n\
+             \tor:\t{} --install [DIR]\n\
+             \tor:\t{} --list\n\
+             \tor:\t{} --man APPLET\n\
+             \tor:\t{} --completion SHELL APPLET\n\n\
+             Run APPLET, or create a symlink in DIR (default: the directory\n\
+             containing this binary) for every applet.\n\n\
+             --man prints APPLET's manual page in roff, and --completion its\n\
+             completion script for SHELL ({}).\n\n\
+             Applets: {}",
+             PROGRAM, PROGRAM, PROGRAM, PROGRAM, PROGRAM,
+             docs::SHELLS.join(", "), applets().join(" "));
+}
+
+// The command line description of the applet named by `name`, reporting
+// unknown applets
+fn applet_command(name: &str) -> Option<Command> {
+    match find_applet(name) {
+        Some(applet) => Some((applet.command)()),
+        None => {
+            let msg = format!("{}: applet not found", name);
+            print_error(PROGRAM, &msg);
+            None
+        }
+    }
+}
+
+// Link every applet name in `dir` (default: next to this binary) to the
+// running binary
+fn install(dir: Option<PathBuf>) {
+    let exe = match env::current_exe() {
+        Ok(p) => p,
+        Err(_) => {
+            print_error(PROGRAM, "cannot determine the path of this binary");
+            return
+        }
+    };
+    let dir = match dir {
+        Some(d) => d,
+        None => exe.parent().unwrap_or(Path::new("/")).to_path_buf()
+    };
+
+    for name in applets() {
+        let link = dir.join(name);
+
+        // Replace links left by a previous install, but never real files
+        match fs::symlink_metadata(&link) {
+            Ok(ref st) if st.file_type().is_symlink() => {
+                let _ = fs::remove_file(&link);
+            },
+            _ => {}
+        }
+
+        if let Err(e) = unix::fs::symlink(&exe, &link) {
+            let msg = format!("cannot create link '{}': {}", link.display(),
+                              common::strerror(&e));
+            print_error(PROGRAM, &msg);
+        }
+    }
+}
+
+fn run(args: Vec<String>) {
+    // Invoked through a link: dispatch on the link name
+    let name = match Path::new(&args[0]).file_name() {
+        Some(s) => s.to_string_lossy().into_owned(),
+        None => String::from(PROGRAM)
+    };
+    if let Some(applet) = find_applet(&name) {
+        (applet.main)(args);
+        return
+    }
+
+    // Otherwise the first argument names the applet
+    if args.len() < 2 {
+        print_usage();
+        return
+    }
+
+    let first = args[1].as_str();
+    match first {
+        "-h" | "--help" => print_usage(),
+        "--list" => {
+            for name in applets().iter() {
+                println!("{}", name);
+            }
+        },
+        "--version" => println!("{} (rusty-coreutils) {}", PROGRAM, common::args::VERSION),
+        "--man" => {
+            if args.len() != 3 {
+                usage_error(PROGRAM, "--man takes one APPLET");
+                return
+            }
+            if let Some(cmd) = applet_command(&args[2]) {
+                print!("{}", docs::man_page(&cmd));
+            }
+        },
+        "--completion" => {
+            if args.len() != 4 {
+                usage_error(PROGRAM, "--completion takes a SHELL and an APPLET");
+                return
+            }
+            let shell = args[2].as_str();
+            if let Some(cmd) = applet_command(&args[3]) {
+                match docs::completion(&cmd, shell) {
+                    Some(script) => print!("{}", script),
+                    None => {
+                        let msg = format!("unsupported shell '{}'", shell);
+                        usage_error(PROGRAM, &msg);
+                    }
+                }
+            }
+        },
+        "--install" => {
+            let dir = if args.len() > 2 {
+                Some(PathBuf::from(&args[2]))
+            } else {
+                None
+            };
+            install(dir);
+        },
+        _ => match find_applet(first) {
+            Some(applet) => (applet.main)(args[1..].to_vec()),
+            None => {
+                let msg = format!("{}: applet not found", first);
+                print_error(PROGRAM, &msg);
+            }
+        }
+    }
+}
+
+fn main() {
+    let args = env::args_os().map(|a| a.to_string_lossy().into_owned()).collect();
+    run(args);
+    process::exit(common::exit_status());
+}
@@ -1,107 +1,101 @@
-extern crate getopts;
-use getopts::{optopt,optflag,getopts,OptGroup};
-use std::os;
+use common::{Command, usage_error, optopt, optflag};
 
-fn print_usage(program: &String, opts: &[OptGroup]) {
-    let desc = format!(
-        "Usage:\t{} [OPTION]... LAST\n\
-            or:\t{} [OPTION]... FIRST LAST\n\
-            or:\t{} [OPTION]... FIRST INCREMENT LAST",
-            program, program, program);
+static PROGRAM: &str = "seq";
 
-    println!("{}", getopts::usage(desc.as_slice(), opts));
+pub fn command() -> Command {
+    Command::new(PROGRAM,
+        &["[OPTION]... LAST", "[OPTION]... FIRST LAST", "[OPTION]... FIRST INCREMENT LAST"],
+        "Print numbers from FIRST to LAST, in steps of INCREMENT.",
+        "",
+        &[
+            optopt("s", "separator", "use STRING to separate numbers", "STRING"),
+            optflag("w", "equal-width", "equalize width by padding with leading zeroes")
+        ])
 }
 
-fn print_error(msg: &str) {
-    println!("seq: {}", msg);
-    os::set_exit_status(1);
-}
-
-fn print_seq(first: int, inc: int, last: int,
-             separator: &String, equal_width: bool) {
+fn print_seq(first: i64, inc: i64, last: i64,
+             separator: &str, equal_width: bool) {
     // If increment does not get closer to last
     if ((last - first) >= 0) != (inc >= 0) {
         return;
     }
 
     // Get max width (for padding)
-    let get_len = |i: int| -> uint {
-        return format!("{}", i).len()
+    let get_len = |i: i64| -> usize {
+        format!("{}", i).len()
     };
     let first_len = get_len(first);
     let last_len = get_len(last);
     let max_width = std::cmp::max(first_len, last_len);
 
     // Generate padded zeroes
-    let generate_padding = |num: uint| {
-        let mut padding = String::from_str("");
-        for _ in range(0, num) {
-            padding.push_str("0");
+    let generate_padding = |num: usize| {
+        let mut padding = String::new();
+        for _ in 0..num {
+            padding.push('0');
         }
-        return padding;
+        padding
     };
 
-    // Loop
-    let mut i: int = first;
+    // Loop: the separator goes between numbers, and a newline after the
+    // last one
+    let mut i: i64 = first;
     while i <= last {
         let padding =
             if !equal_width {
-                String::from_str("")
+                String::new()
             } else {
                 generate_padding(max_width - get_len(i))
             };
 
-        print!("{}{}{}", padding, i, separator);
-        i = i + inc;
+        if i != first {
+            print!("{}", separator);
+        }
+        print!("{}{}", padding, i);
+        i += inc;
+    }
+    if first <= last {
+        println!();
     }
 }
 
-fn main() {
-    let args = os::args();
-    let program = args.get(0).clone();
-
-    let opts = [
-        optopt("s", "separator", "use STRING to separate numbers", "STRING"),
-        optflag("w", "equal-width", "equalize width by padding with leading zeroes"),
-        optflag("h", "help", "display this help and exit")
-    ];
-    let matches = match getopts(args.tail(), opts) {
-        Ok(m) => { m }
-        Err(f) => { fail!(f) }
+pub fn uumain(args: Vec<String>) {
+    let matches = match command().parse(&args[1..]) {
+        Some(m) => m,
+        None => return
     };
 
     let separator = match matches.opt_str("s") {
         Some(a) => a,
-        None => String::from_str("\n")
+        None => String::from("\n")
     };
     let equal_width = matches.opt_present("w");
-    let help = matches.opt_present("h");
 
     let free = matches.free.clone();
     let len = free.len();
 
-    if help {
-        print_usage(&program, opts);
-    } else if len == 0 {
-        print_error("missing operand");
+    if len == 0 {
+        usage_error(PROGRAM, "missing operand");
     } else if len > 3 {
-        print_error("extra operand");
+        let msg = format!("extra operand '{}'", free[3]);
+        usage_error(PROGRAM, &msg);
     } else {
-        let get_arg = |i: uint| -> Option<int> {
-            from_str(free.get(i).as_slice())
-        };
-        let (first_opt, inc_opt, last_opt) = match len {
-            1 => (Some(1), Some(1), get_arg(0)),
-            2 => (get_arg(0), Some(1), get_arg(1)),
-            3 => (get_arg(0), get_arg(1), get_arg(2)),
-            _ => return
-        };
-        let (first, inc, last) = match (first_opt, inc_opt, last_opt) {
-            (Some(a), Some(b), Some(c)) => (a, b, c),
-            _ => {
-                print_error("invalid floating point argument");
-                return
+        let mut nums = Vec::new();
+        for arg in free.iter() {
+            match arg.parse::<i64>() {
+                Ok(n) => nums.push(n),
+                Err(_) => {
+                    let msg = format!("invalid floating point argument: '{}'", arg);
+                    usage_error(PROGRAM, &msg);
+                    return
+                }
             }
+        }
+        let get_arg = |i: usize| -> i64 { nums[i] };
+        let (first, inc, last) = match len {
+            1 => (1, 1, get_arg(0)),
+            2 => (get_arg(0), 1, get_arg(1)),
+            _ => (get_arg(0), get_arg(1), get_arg(2))
         };
 
         print_seq(first, inc, last, &separator, equal_width);
@@ -0,0 +1,129 @@
+//! Vector instructions for the fast paths of the programs that have one,
+//! and which of them the processor offers. Only those programs link it.
+//!
+//! The compiler only uses the instructions every x86-64 processor has, up
+//! to SSE2. Later instructions (SSSE3's byte shuffle, and AVX2 with its
+//! 32-byte vectors) are used from functions compiled for them with
+//! `#[target_feature]`, and a program only calls those once `cpuid` has
+//! shown that the processor has them. Each program lists its kernels with
+//! the features they need, and `fastest` picks the one to use. On other
+//! processors nothing is offered, and the programs keep to their portable
+//! loops.
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::asm;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::{__m128i, _mm_loadu_si128, _mm_set1_epi8, _mm_storeu_si128};
+
+/// A vector of 16 copies of `b`.
+#[cfg(target_arch = "x86_64")]
+pub fn splat(b: u8) -> __m128i {
+    unsafe { _mm_set1_epi8(b as i8) }
+}
+
+/// The first 16 bytes of `s`, which need not be aligned.
+#[cfg(target_arch = "x86_64")]
+pub fn load(s: &[u8]) -> __m128i {
+    let s = &s[..16];
+    unsafe { _mm_loadu_si128(s.as_ptr() as *const __m128i) }
+}
+
+/// The bytes of `v`, first lane first.
+#[cfg(target_arch = "x86_64")]
+pub fn to_bytes(v: __m128i) -> [u8; 16] {
+    let mut a = [0u8; 16];
+    unsafe { _mm_storeu_si128(a.as_mut_ptr() as *mut __m128i, v) };
+    a
+}
+
+// The registers `cpuid` returns for `leaf`: eax, ebx, ecx and edx
+#[cfg(target_arch = "x86_64")]
+fn cpuid(leaf: u32) -> [u32; 4] {
+    let (a, b, c, d): (u32, u64, u32, u32);
+    // LLVM keeps rbx for itself, so it is swapped out around the instruction
+    unsafe {
+        asm!("mov {b:r}, rbx",
+             "cpuid",
+             "xchg {b:r}, rbx",
+             b = out(reg) b,
+             inout("eax") leaf => a,
+             inout("ecx") 0u32 => c,
+             out("edx") d,
+             options(nostack, preserves_flags));
+    }
+    [a, b as u32, c, d]
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn cpuid(_: u32) -> [u32; 4] {
+    [0, 0, 0, 0]
+}
+
+// The register state the operating system saves for each task
+#[cfg(target_arch = "x86_64")]
+fn xgetbv() -> u32 {
+    let state: u32;
+    unsafe {
+        asm!("xgetbv", in("ecx") 0u32, out("eax") state, out("edx") _,
+             options(nomem, nostack, preserves_flags));
+    }
+    state
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn xgetbv() -> u32 {
+    0
+}
+
+/// An instruction set extension a kernel may need.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Feature {
+    /// Every x86-64 processor has it
+    Sse2,
+    /// For byte shuffles
+    Ssse3,
+    Popcnt,
+    /// Only counted when the operating system saves the 256-bit registers
+    Avx2
+}
+
+/// Whether the processor has `feature`, so that code using it can run.
+pub fn has(feature: Feature) -> bool {
+    match feature {
+        Feature::Sse2 => cfg!(target_arch = "x86_64"),
+        Feature::Ssse3 => cpuid(1)[2] & (1 << 9) != 0,
+        Feature::Popcnt => cpuid(1)[2] & (1 << 23) != 0,
+        Feature::Avx2 => has_avx2()
+    }
+}
+
+fn has_avx2() -> bool {
+    if cpuid(0)[0] < 7 {
+        return false;
+    }
+    // AVX, and XGETBV to ask about the registers
+    let ecx = cpuid(1)[2];
+    if ecx & (1 << 27) == 0 || ecx & (1 << 28) == 0 {
+        return false;
+    }
+    // The SSE and AVX registers
+    if xgetbv() & 6 != 6 {
+        return false;
+    }
+    cpuid(7)[1] & (1 << 5) != 0
+}
+
+/// Of `kernels`, each listed with the features it needs and the slowest
+/// first, those this processor can run, in the same order.
+pub fn runnable<K: Copy>(kernels: &[(K, &[Feature])]) -> Vec<K> {
+    kernels.iter()
+           .filter(|&&(_, needs)| needs.iter().all(|&f| has(f)))
+           .map(|&(k, _)| k)
+           .collect()
+}
+
+/// The fastest of `kernels`, as given to `runnable`, that this processor
+/// can run. The first kernel must need nothing.
+pub fn fastest<K: Copy>(kernels: &[(K, &[Feature])]) -> K {
+    *runnable(kernels).last().unwrap()
+}
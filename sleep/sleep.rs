@@ -1,26 +1,32 @@
-extern crate getopts;
-use getopts::{optflag,getopts,OptGroup};
-use std::{os};
+use common::Command;
+use std::thread;
+use std::time::Duration;
 
-fn print_usage(program: &String, opts: &[OptGroup]) {
-    let desc = format!(
-        "Usage:\t{} NUMBER[SUFFIX]\n\
-        \t{} OPTION",
-        program, program);
+static PROGRAM: &str = "sleep";
 
-    println!("{}", getopts::usage(desc.as_slice(), opts));
+pub fn command() -> Command {
+    Command::new(PROGRAM,
+        &["NUMBER[SUFFIX]", "OPTION"],
+        "Pause for NUMBER seconds.",
+        "SUFFIX may be 's' for seconds (the default), 'm' for minutes, \
+        'h' for hours or 'd' for days.",
+        &[])
 }
 
-fn print_error(err: &str) {
-    println!("sleep: {}", err);
-    os::set_exit_status(1);
+fn invalid_interval(time_str: &str) {
+    let msg = format!("invalid time interval '{}'", time_str);
+    common::usage_error(PROGRAM, &msg);
 }
 
 fn sleep(time_str: &str) {
-    let last_index = time_str.len() - 1;
-    let last_char = time_str.char_at(last_index);
+    if time_str.is_empty() {
+        invalid_interval(time_str);
+        return;
+    }
+
+    let last_char = time_str.chars().last().unwrap();
 
-    let prefix_str = time_str.slice_to(last_index);
+    let prefix_str = &time_str[..time_str.len() - last_char.len_utf8()];
 
     let (duration_str, multiplier) = match last_char {
         'd' => (prefix_str, 24 * 60 * 60 * 1000),
@@ -30,42 +36,32 @@ fn sleep(time_str: &str) {
         _ => (time_str, 1000)
     };
 
-    let duration: Option<u64> = from_str(duration_str);
+    let duration: Option<u64> = duration_str.parse().ok();
     let duration_u64 = match duration {
         Some(f) => f * multiplier,
         _ => {
-            let error = format!("Invalid time interval: {}", time_str);
-            print_error(error.as_slice());
+            invalid_interval(time_str);
             return;
         }
     };
 
-    std::io::timer::sleep(duration_u64);
+    thread::sleep(Duration::from_millis(duration_u64));
 }
 
-fn main() {
-    let args = os::args();
-    let program = args.get(0).clone();
-
-    let opts = [
-        optflag("h", "help", "display this help and exit")
-    ];
-    let matches = match getopts(args.tail(), opts) {
-        Ok(m) => { m }
-        Err(f) => { fail!(f) }
+pub fn uumain(args: Vec<String>) {
+    let matches = match command().parse(&args[1..]) {
+        Some(m) => m,
+        None => return
     };
 
-    let help = matches.opt_present("h");
     let free = matches.free.clone();
     let len = free.len();
 
-    if help {
-        print_usage(&program, opts);
-    } else if len == 0 {
-        print_error("missing operand");
+    if len == 0 {
+        common::usage_error(PROGRAM, "missing operand");
     } else {
         // let a = "1.2";
-        let arg = free.get(0);
-        sleep(arg.as_slice());
+        let arg = &free[0];
+        sleep(arg);
     }
 }
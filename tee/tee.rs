@@ -1,58 +1,73 @@
-extern crate getopts;
-use getopts::{optflag,getopts,OptGroup};
-use std::{io,os};
+use common::{Command, print_file_error, optflag};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
 
-static PROGRAM: &'static str = "tee";
+static PROGRAM: &str = "tee";
 
-fn print_usage(opts: &[OptGroup]) {
-    let desc = format!(
-        "Usage:\t{} [OPTION]... [FILE]...",
-            PROGRAM);
-
-    println!("{}", getopts::usage(desc.as_slice(), opts));
+struct Output<'a> {
+    name: &'a str,
+    file: Option<File>
 }
 
-fn read_stdin() -> String {
-    let mut s = String::from_str("");
-    for line in io::stdin().lines() {
-        s.push_str(line.unwrap().as_slice());
-    }
-    return s;
+pub fn command() -> Command {
+    Command::new(PROGRAM,
+        &["[OPTION]... [FILE]..."],
+        "Copy standard input to each FILE, and also to standard output.",
+        "",
+        &[
+            optflag("a", "append", "append to given FILEs, do not overwrite")
+        ])
 }
 
-fn main() {
-    let args = os::args();
-
-    let opts = [
-        optflag("a", "append", "append to given FILEs, do not overwrite"),
-        optflag("h", "help", "display this help and exit")
-    ];
-    let matches = match getopts(args.tail(), opts) {
-        Ok(m) => { m }
-        Err(f) => { fail!(f) }
+pub fn uumain(args: Vec<String>) {
+    let matches = match command().parse(&args[1..]) {
+        Some(m) => m,
+        None => return
     };
 
-    let help = matches.opt_present("h");
     let free = matches.free.clone();
 
-    if help {
-        print_usage(opts);
-    } else {
-        let stdin_str = read_stdin();
+    let append = matches.opt_present("a");
+    let mut open_options = OpenOptions::new();
+    open_options.write(true).create(true).append(append).truncate(!append);
+
+    let mut outputs = Vec::new();
+    for out_file in free.iter() {
+        match open_options.open(out_file) {
+            Ok(f) => outputs.push(Output { name: out_file, file: Some(f) }),
+            Err(e) => print_file_error(PROGRAM, out_file, &e)
+        }
+    }
 
-        print!("{}", &stdin_str);
+    // Copy standard input to stdout and every file as it arrives. An
+    // output that fails is reported and dropped; the others carry on.
+    let mut stdin = io::stdin().lock();
+    let mut stdout = Some(io::stdout().lock());
+    let result = common::for_each_chunk(&mut stdin, |chunk| {
+        let failed = match stdout {
+            Some(ref mut out) => out.write_all(chunk).and_then(|_| out.flush()).err(),
+            None => None
+        };
+        if let Some(e) = failed {
+            print_file_error(PROGRAM, "standard output", &e);
+            stdout = None;
+        }
 
-        let write_mode =
-            if matches.opt_present("a") {
-                std::io::Append
-            } else {
-                std::io::Truncate
+        for output in outputs.iter_mut() {
+            let failed = match output.file {
+                Some(ref mut f) => f.write_all(chunk).err(),
+                None => None
             };
-
-        for out_file in free.iter() {
-            let path = Path::new(out_file.as_slice());
-            let mut write_only = std::io::File::open_mode(&path, write_mode, std::io::Write);
-            write_only.write(stdin_str.as_bytes()).unwrap();
+            if let Some(e) = failed {
+                print_file_error(PROGRAM, output.name, &e);
+                output.file = None;
+            }
         }
+        Ok(())
+    });
+    if let Err(e) = result {
+        let msg = format!("read error: {}", common::strerror(&e));
+        common::print_error(PROGRAM, &msg);
     }
 }
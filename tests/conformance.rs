@@ -0,0 +1,149 @@
+//! Runs every case under tests/cases against the multicall binary and
+//! compares stdout, stderr and exit status with the results recorded from
+//! GNU coreutils by tests/record.sh.
+//!
+//! A case is a directory tests/cases/PROGRAM/NAME holding:
+//!
+//! * `args`: the arguments, one per line
+//! * `stdin`: optional, fed to standard input
+//! * `env`: optional, NAME=VALUE lines added to the environment, which
+//!   otherwise has LC_ALL=C as when recording
+//! * `full`: optional, standard output goes to /dev/full so that writing
+//!   it fails, and `stdout` is empty
+//! * `stdout`, `stderr`, `status`: the recorded results
+//! * `xfail`: optional, why the case is known not to pass yet
+//! * `manual`: optional, where the expected results were written by hand
+//!   because GNU coreutils has no such feature; says where they came from
+//!
+//! Cases run from tests/fixtures, so arguments can name the files there.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn read(path: &Path) -> Vec<u8> {
+    match fs::read(path) {
+        Ok(v) => v,
+        Err(e) => panic!("{}: {}", path.display(), e)
+    }
+}
+
+fn read_str(path: &Path) -> String {
+    String::from_utf8_lossy(&read(path)).into_owned()
+}
+
+fn coreutils_bin() -> PathBuf {
+    PathBuf::from(env!("CARGO_BIN_EXE_coreutils"))
+}
+
+// Programs built into the binary; the others are skipped
+fn applets(bin: &Path) -> Vec<String> {
+    let output = Command::new(bin).arg("--list").output().unwrap();
+    let list = String::from_utf8(output.stdout).unwrap();
+    list.lines().map(String::from).collect()
+}
+
+fn run_case(bin: &Path, fixtures: &Path, program: &str, case: &Path) -> Result<(), String> {
+    let args_str = read_str(&case.join("args"));
+    let args: Vec<&str> = args_str.lines().collect();
+
+    // For a `full` case a shell redirects standard output, then runs the
+    // program in its place
+    let mut command = if case.join("full").exists() {
+        let mut c = Command::new("sh");
+        c.arg("-c").arg("exec \"$0\" \"$@\" >/dev/full").arg(bin);
+        c
+    } else {
+        Command::new(bin)
+    };
+    command.arg(program).args(&args).current_dir(fixtures).env("LC_ALL", "C");
+    let env_path = case.join("env");
+    if env_path.exists() {
+        for line in read_str(&env_path).lines() {
+            if let Some(i) = line.find('=') {
+                command.env(&line[..i], &line[i + 1..]);
+            }
+        }
+    }
+    command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    let mut process = match command.spawn() {
+        Ok(p) => p,
+        Err(e) => return Err(format!("cannot run {}: {}", bin.display(), e))
+    };
+
+    // Dropping the pipe closes standard input
+    {
+        let stdin_path = case.join("stdin");
+        let input = if stdin_path.exists() { read(&stdin_path) } else { Vec::new() };
+        let mut stdin = process.stdin.take().unwrap();
+        let _ = stdin.write_all(&input);
+    }
+
+    let Output { status, stdout, stderr } = process.wait_with_output().unwrap();
+
+    let expected_status: i32 = read_str(&case.join("status")).trim().parse().unwrap();
+    let mut problems = Vec::new();
+    if status.code() != Some(expected_status) {
+        problems.push(format!("{}, expected exit code: {}", status, expected_status));
+    }
+    if stdout != read(&case.join("stdout")) {
+        problems.push(format!("stdout was:\n{}", String::from_utf8_lossy(&stdout)));
+    }
+    if stderr != read(&case.join("stderr")) {
+        problems.push(format!("stderr was:\n{}", String::from_utf8_lossy(&stderr)));
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.join("\n"))
+    }
+}
+
+// The entries of a directory, in order
+fn entries(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir).unwrap().map(|e| e.unwrap().path()).collect();
+    paths.sort();
+    paths
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name().unwrap().to_str().unwrap()
+}
+
+#[test]
+fn conformance() {
+    let tests = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let fixtures = tests.join("fixtures");
+    let bin = coreutils_bin();
+    let applets = applets(&bin);
+
+    let mut failures = Vec::new();
+    for program_dir in entries(&tests.join("cases")) {
+        let program = file_name(&program_dir).to_string();
+        if !applets.contains(&program) {
+            continue;
+        }
+
+        for case in entries(&program_dir) {
+            let name = format!("{}/{}", program, file_name(&case));
+
+            let xfail = case.join("xfail");
+            if xfail.exists() {
+                println!("{}: known failure: {}", name, read_str(&xfail).trim());
+                continue;
+            }
+
+            if let Err(e) = run_case(&bin, &fixtures, &program, &case) {
+                failures.push(format!("{}: {}", name, e));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        panic!("{} case(s) differ from GNU coreutils:\n\n{}",
+               failures.len(), failures.join("\n\n"));
+    }
+}
@@ -0,0 +1,47 @@
+#!/bin/bash
+# Record the expected stdout, stderr and exit status of test cases by
+# running the GNU coreutils programs found on PATH.
+#
+# Usage: tests/record.sh [CASE_DIR]...   (default: every case)
+#
+# Cases marked `manual` have no GNU counterpart to record from and are left
+# alone.
+
+set -e
+
+tests=$(cd "$(dirname "$0")" && pwd)
+export LC_ALL=C
+
+if [ $# -eq 0 ]; then
+    set -- "$tests"/cases/*/*
+fi
+
+for case in "$@"; do
+    case=$(cd "$case" && pwd)
+    if [ -f "$case/manual" ]; then
+        continue
+    fi
+    program=$(basename "$(dirname "$case")")
+    mapfile -t args < "$case/args"
+
+    env=()
+    if [ -f "$case/env" ]; then
+        mapfile -t env < "$case/env"
+    fi
+
+    stdin=/dev/null
+    if [ -f "$case/stdin" ]; then
+        stdin="$case/stdin"
+    fi
+
+    stdout="$case/stdout"
+    if [ -f "$case/full" ]; then
+        stdout=/dev/full
+        : > "$case/stdout"
+    fi
+
+    status=0
+    (cd "$tests/fixtures" && env "${env[@]}" "$program" "${args[@]}") \
+        < "$stdin" > "$stdout" 2> "$case/stderr" || status=$?
+    echo "$status" > "$case/status"
+done
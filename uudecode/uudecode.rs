@@ -0,0 +1,210 @@
+use common::{Command, Output, print_error, print_file_error, optopt};
+use base64::{codec, uu};
+use std::fs::{OpenOptions, Permissions};
+use std::io;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+static PROGRAM: &str = "uudecode";
+
+pub fn command() -> Command {
+    Command::new(PROGRAM,
+        &["[OPTION]... [FILE]..."],
+        "Decode each FILE, or standard input, written by uuencode.",
+        "The decoded data go to the file named on the 'begin' line, which must \
+        not\nexist yet, created with the mode given there. A file given with -o \
+        is\nreplaced if it exists. An output file of - or /dev/stdout is \
+        standard output.",
+        &[
+            optopt("o", "output-file", "write to OUTFILE rather than the file named in the input",
+                   "OUTFILE")
+        ])
+}
+
+type Input = BufReader<Box<dyn Read>>;
+
+// Next line of `input` without its line ending, or None at end of input
+fn next_line(input: &mut Input) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    if input.read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+    while line.last() == Some(&b'\n') || line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+struct Header {
+    base64: bool,
+    mode: u32,
+    name: String
+}
+
+// "begin MODE NAME" or "begin-base64 MODE NAME"
+fn parse_header(line: &[u8]) -> Option<Header> {
+    let line = String::from_utf8_lossy(line);
+    let mut words = line.splitn(3, ' ');
+    let base64 = match words.next() {
+        Some("begin") => false,
+        Some("begin-base64") => true,
+        _ => return None
+    };
+    let mode = match words.next().and_then(|m| u32::from_str_radix(m, 8).ok()) {
+        Some(m) => m & 0o777,
+        None => return None
+    };
+    match words.next() {
+        Some(name) if !name.is_empty() => {
+            Some(Header { base64, mode, name: String::from(name) })
+        },
+        _ => None
+    }
+}
+
+fn is_stdout(name: &str) -> bool {
+    name == "-" || name == "/dev/stdout"
+}
+
+// Open the output with `mode`. A name from the input is only ever created,
+// so that an input cannot overwrite a file, or write through a symbolic
+// link; the file is given exactly `mode`, whatever the umask. A name the
+// user gave with -o is written as it is, as by the shell's `>`.
+fn open_output(name: &str, mode: u32, from_input: bool) -> io::Result<Box<dyn Write>> {
+    if is_stdout(name) {
+        return Ok(Box::new(io::stdout()));
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).mode(mode);
+    if from_input {
+        options.create_new(true);
+    } else {
+        options.create(true).truncate(true);
+    }
+    let file = options.open(name)?;
+    if from_input {
+        file.set_permissions(Permissions::from_mode(mode))?;
+    }
+    Ok(Box::new(file))
+}
+
+fn missing_end() -> io::Result<()> {
+    Err(codec::invalid_input(String::from("no 'end' line")))
+}
+
+fn decode_traditional(input: &mut Input, out: &mut dyn Write) -> io::Result<()> {
+    let mut data = Vec::new();
+    loop {
+        let line = match next_line(input)? {
+            Some(l) => l,
+            None => return missing_end()
+        };
+        if uu::decode_line(&line, &mut data) == 0 {
+            break;
+        }
+        out.write_all(&data)?;
+        data.clear();
+    }
+
+    match next_line(input)? {
+        Some(ref l) if l == b"end" => Ok(()),
+        _ => missing_end()
+    }
+}
+
+fn decode_base64(input: &mut Input, out: &mut dyn Write) -> io::Result<()> {
+    let mut decoder = codec::Decoder::new(&codec::BASE64, false);
+    loop {
+        let line = match next_line(input)? {
+            Some(l) => l,
+            None => return missing_end()
+        };
+        if line == b"====" {
+            return decoder.finish(out);
+        }
+        decoder.update(&line, out)?;
+    }
+}
+
+// Skip to the first header in `input`
+fn find_header(input: &mut Input) -> io::Result<Option<Header>> {
+    while let Some(line) = next_line(input)? {
+        if let Some(h) = parse_header(&line) {
+            return Ok(Some(h));
+        }
+    }
+    Ok(None)
+}
+
+// Decode the first encoded file in `input`
+fn decode(input: &mut Input, filename: &str, output: Option<&str>) {
+    let header = match find_header(input) {
+        Ok(Some(h)) => h,
+        Ok(None) => {
+            let msg = format!("{}: no 'begin' line", filename);
+            print_error(PROGRAM, &msg);
+            return
+        },
+        Err(e) => {
+            print_file_error(PROGRAM, filename, &e);
+            return
+        }
+    };
+
+    let name = match output {
+        Some(o) => String::from(o),
+        None => header.name.clone()
+    };
+    let mut out = match open_output(&name, header.mode, output.is_none()) {
+        Ok(o) => Output::new(o),
+        Err(e) => {
+            print_file_error(PROGRAM, &name, &e);
+            return
+        }
+    };
+
+    let result =
+        if header.base64 {
+            decode_base64(input, &mut out)
+        } else {
+            decode_traditional(input, &mut out)
+        };
+    let result = result.and_then(|_| out.flush());
+
+    match result {
+        Ok(_) => {},
+        Err(ref e) if out.failed && is_stdout(&name) => {
+            let msg = format!("write error: {}", common::strerror(e));
+            print_error(PROGRAM, &msg);
+        },
+        Err(e) if out.failed => print_file_error(PROGRAM, &name, &e),
+        Err(ref e) if e.kind() == ErrorKind::InvalidData => {
+            let msg = format!("{}: invalid input: {}", filename, common::strerror(e));
+            print_error(PROGRAM, &msg);
+        },
+        Err(e) => print_file_error(PROGRAM, filename, &e)
+    }
+}
+
+pub fn uumain(args: Vec<String>) {
+    let matches = match command().parse(&args[1..]) {
+        Some(m) => m,
+        None => return
+    };
+
+    let mut files = matches.free.clone();
+    if files.is_empty() {
+        files.push(String::from("-"));
+    }
+    let output = matches.opt_str("o");
+
+    for filename in files.iter() {
+        match common::open_input(filename) {
+            Ok(r) => {
+                let mut input = BufReader::new(r);
+                decode(&mut input, filename, output.as_deref());
+            },
+            Err(e) => print_file_error(PROGRAM, filename, &e)
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use common::{Command, Output, print_error, print_file_error, usage_error, optflag};
+use base64::{codec, uu};
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::os::unix::fs::PermissionsExt;
+
+static PROGRAM: &str = "uuencode";
+
+// Lines of the base64 body are as long as traditional ones
+const BASE64_WRAP: usize = 60;
+
+pub fn command() -> Command {
+    Command::new(PROGRAM,
+        &["[OPTION]... [INFILE] REMOTEFILE"],
+        "Encode INFILE, or standard input, for transmission as REMOTEFILE.",
+        "The output starts with a 'begin' line naming REMOTEFILE and its mode, \
+        which uudecode uses to recreate the file.",
+        &[
+            optflag("m", "base64", "encode with base64 rather than the traditional encoding")
+        ])
+}
+
+fn encode_traditional(reader: &mut dyn Read, out: &mut dyn Write) -> io::Result<()> {
+    let mut pending = Vec::new();
+    let mut text = Vec::new();
+    common::for_each_chunk(reader, |chunk| {
+        pending.extend_from_slice(chunk);
+        let mut start = 0;
+        while pending.len() - start >= uu::LINE_BYTES {
+            uu::encode_line(&pending[start..start + uu::LINE_BYTES], &mut text);
+            start += uu::LINE_BYTES;
+        }
+        pending.drain(..start);
+        out.write_all(&text)?;
+        text.clear();
+        Ok(())
+    })?;
+
+    if !pending.is_empty() {
+        uu::encode_line(&pending, &mut text);
+    }
+    // An empty line ends the data
+    uu::encode_line(&[], &mut text);
+    out.write_all(&text)?;
+    out.write_all(b"end\n")
+}
+
+fn encode_base64(reader: &mut dyn Read, out: &mut dyn Write) -> io::Result<()> {
+    let mut config = codec::Config::new(&codec::BASE64);
+    config.wrap = BASE64_WRAP;
+    codec::encode(reader, out, &config)?;
+    out.write_all(b"====\n")
+}
+
+fn encode(reader: &mut dyn Read, out: &mut dyn Write, remote: &str, mode: u32,
+          base64: bool) -> io::Result<()> {
+    let header = if base64 { "begin-base64" } else { "begin" };
+    writeln!(out, "{} {:o} {}", header, mode, remote)?;
+    if base64 {
+        encode_base64(reader, out)?;
+    } else {
+        encode_traditional(reader, out)?;
+    }
+    out.flush()
+}
+
+pub fn uumain(args: Vec<String>) {
+    let matches = match command().parse(&args[1..]) {
+        Some(m) => m,
+        None => return
+    };
+
+    let free = &matches.free;
+    let (infile, remote) = match free.len() {
+        0 => {
+            usage_error(PROGRAM, "missing operand");
+            return
+        },
+        1 => ("-", free[0].as_str()),
+        2 => (free[0].as_str(), free[1].as_str()),
+        _ => {
+            let msg = format!("extra operand '{}'", free[2]);
+            usage_error(PROGRAM, &msg);
+            return
+        }
+    };
+
+    // Standard input is recorded as a file created under the usual umask
+    let mode = if infile == "-" {
+        0o644
+    } else {
+        match fs::metadata(infile) {
+            Ok(st) => st.permissions().mode() & 0o777,
+            Err(e) => {
+                print_file_error(PROGRAM, infile, &e);
+                return
+            }
+        }
+    };
+
+    let mut reader = match common::open_input(infile) {
+        Ok(r) => r,
+        Err(e) => {
+            print_file_error(PROGRAM, infile, &e);
+            return
+        }
+    };
+
+    let mut out = Output::new(io::stdout().lock());
+    match encode(&mut *reader, &mut out, remote, mode, matches.opt_present("m")) {
+        Ok(_) => {},
+        Err(ref e) if out.failed => {
+            let msg = format!("write error: {}", common::strerror(e));
+            print_error(PROGRAM, &msg);
+        },
+        Err(e) => print_file_error(PROGRAM, infile, &e)
+    }
+}
@@ -0,0 +1,353 @@
+//! Counting newlines and words many bytes at a time.
+//!
+//! Words are only counted this way in blocks of printable ASCII and ASCII
+//! whitespace, where every byte either is part of a word or separates
+//! words; blocks holding anything else are left to the scalar counter.
+//!
+//! The portable loop takes 8 bytes at a time: they are loaded into a u64
+//! and classified together with carry-free arithmetic that sets the high
+//! bit of each byte of a mask where the byte matches, so a count is the
+//! number of bits set. The vector loops classify 16 bytes with SSE2, or 32
+//! with AVX2 where the processor has it (see the `simd` crate), the same way:
+//! adding a constant to each byte sets its high bit from some value up, and
+//! the high bits are gathered into a mask.
+
+use simd::Feature;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+// The low and high bit of every byte
+const LO: u64 = 0x0101010101010101;
+const HI: u64 = 0x8080808080808080;
+
+/// How blocks are counted.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Kernel {
+    /// A byte at a time, by the scalar counter
+    Scalar,
+    /// 8 bytes at a time in a u64
+    Swar,
+    /// 16 bytes at a time, then as Swar
+    Sse2,
+    /// 32 bytes at a time, then as Swar
+    Avx2
+}
+
+use self::Kernel::*;
+
+// Each kernel with what it needs, the slowest first
+static KERNELS: &[(Kernel, &[Feature])] = &[
+    (Scalar, &[]),
+    (Swar, &[]),
+    (Sse2, &[Feature::Sse2]),
+    (Avx2, &[Feature::Avx2, Feature::Popcnt])
+];
+
+/// The kernels this processor can run, the fastest last, for the tests.
+#[cfg(test)]
+pub fn kernels() -> Vec<Kernel> {
+    simd::runnable(KERNELS)
+}
+
+/// The fastest kernel this processor can run.
+pub fn best() -> Kernel {
+    simd::fastest(KERNELS)
+}
+
+/// The 8 bytes at the start of `s`, the first in the low byte.
+pub fn load(s: &[u8]) -> u64 {
+    let mut x = [0u8; 8];
+    x.copy_from_slice(&s[..8]);
+    u64::from_le_bytes(x)
+}
+
+// The high bit of each byte that is zero
+fn zero_bytes(x: u64) -> u64 {
+    !(((x & !HI) + !HI) | x | !HI)
+}
+
+// The high bit of each byte that is at least `n`, for bytes below 0x80 and
+// `n` at most 0x80
+fn at_least(x: u64, n: u8) -> u64 {
+    ((x | HI) - LO * n as u64) & HI
+}
+
+/// The high bit of each byte of `x` that is a newline.
+pub fn newlines(x: u64) -> u64 {
+    zero_bytes(x ^ (LO * b'\n' as u64))
+}
+
+/// Whether every byte of `x` is printable ASCII or ASCII whitespace.
+pub fn is_plain(x: u64) -> bool {
+    if x & HI != 0 {
+        return false;
+    }
+    let printable = at_least(x, 0x20) & !at_least(x, 0x7F);
+    let space = at_least(x, 0x09) & !at_least(x, 0x0E);
+    printable | space == HI
+}
+
+/// The high bit of each byte of a plain block `x` that is part of a word:
+/// printable and not a space.
+pub fn word_bytes(x: u64) -> u64 {
+    at_least(x, 0x21) & !at_least(x, 0x7F)
+}
+
+/// The number of words starting in the plain block with word bytes
+/// `words`, given whether the byte before it was in a word.
+pub fn word_starts(words: u64, in_word: bool) -> usize {
+    let before = (words << 8) | if in_word { 0x80 } else { 0 };
+    (words & !before).count_ones() as usize
+}
+
+/// What the plain blocks at the start of a text came to.
+pub struct Plain {
+    /// How many bytes they were, a multiple of 8
+    pub len: usize,
+    pub lines: usize,
+    pub words: usize,
+    /// Whether the last byte was in a word
+    pub in_word: bool
+}
+
+/// Count the blocks of 8 bytes at the start of `s` up to the first that is
+/// not plain, given whether the byte before them was in a word. The Scalar
+/// kernel counts none.
+pub fn count_plain(kernel: Kernel, s: &[u8], in_word: bool) -> Plain {
+    let mut plain = Plain { len: 0, lines: 0, words: 0, in_word };
+    match kernel {
+        Scalar => return plain,
+        Swar => {}
+        Sse2 => plain_sse2(s, &mut plain),
+        Avx2 => unsafe { plain_avx2(s, &mut plain) }
+    }
+    plain_swar(s, &mut plain);
+    plain
+}
+
+// Carry on from `plain.len` until a block is not plain
+fn plain_swar(s: &[u8], plain: &mut Plain) {
+    while s.len() - plain.len >= 8 {
+        let x = load(&s[plain.len..]);
+        if !is_plain(x) {
+            break;
+        }
+        let words = word_bytes(x);
+        plain.lines += newlines(x).count_ones() as usize;
+        plain.words += word_starts(words, plain.in_word);
+        plain.in_word = words >> 63 != 0;
+        plain.len += 8;
+    }
+}
+
+// Each byte of `c` plus `n`
+#[cfg(target_arch = "x86_64")]
+fn add(c: __m128i, n: u8) -> __m128i {
+    unsafe { _mm_add_epi8(c, simd::splat(n)) }
+}
+
+// The high bits of `a & !b`
+#[cfg(target_arch = "x86_64")]
+fn mask_and_not(a: __m128i, b: __m128i) -> u32 {
+    unsafe { _mm_movemask_epi8(_mm_andnot_si128(b, a)) as u32 }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn plain_sse2(s: &[u8], plain: &mut Plain) {
+    while s.len() - plain.len >= 16 {
+        let c = simd::load(&s[plain.len..]);
+        let one = add(c, 0x01);
+        let (ascii, printable, space, words, lines) = unsafe {
+            (_mm_movemask_epi8(c),
+             _mm_andnot_si128(one, add(c, 0x60)),
+             _mm_andnot_si128(add(c, 0x72), add(c, 0x77)),
+             mask_and_not(add(c, 0x5F), one),
+             _mm_movemask_epi8(_mm_cmpeq_epi8(c, simd::splat(b'\n'))))
+        };
+        if ascii != 0 || unsafe { _mm_movemask_epi8(_mm_or_si128(printable, space)) } != 0xFFFF {
+            break;
+        }
+        let before = (words << 1) | if plain.in_word { 1 } else { 0 };
+        plain.lines += lines.count_ones() as usize;
+        plain.words += (words & !before).count_ones() as usize;
+        plain.in_word = words >> 15 != 0;
+        plain.len += 16;
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn plain_sse2(_: &[u8], _: &mut Plain) {}
+
+// As plain_sse2, 32 bytes a step. Needs AVX2 and POPCNT.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,popcnt")]
+unsafe fn plain_avx2(s: &[u8], plain: &mut Plain) {
+    let splat = |n: u8| _mm256_set1_epi8(n as i8);
+    while s.len() - plain.len >= 32 {
+        let c = _mm256_loadu_si256(s[plain.len..].as_ptr() as *const __m256i);
+        if _mm256_movemask_epi8(c) != 0 {
+            break;
+        }
+        let one = _mm256_add_epi8(c, splat(0x01));
+        let printable = _mm256_andnot_si256(one, _mm256_add_epi8(c, splat(0x60)));
+        let space = _mm256_andnot_si256(_mm256_add_epi8(c, splat(0x72)),
+                                        _mm256_add_epi8(c, splat(0x77)));
+        if _mm256_movemask_epi8(_mm256_or_si256(printable, space)) != -1 {
+            break;
+        }
+        let words = _mm256_movemask_epi8(
+            _mm256_andnot_si256(one, _mm256_add_epi8(c, splat(0x5F)))) as u32;
+        let lines = _mm256_movemask_epi8(_mm256_cmpeq_epi8(c, splat(b'\n'))) as u32;
+        let before = (words << 1) | if plain.in_word { 1 } else { 0 };
+        plain.lines += lines.count_ones() as usize;
+        plain.words += (words & !before).count_ones() as usize;
+        plain.in_word = words >> 31 != 0;
+        plain.len += 32;
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+unsafe fn plain_avx2(_: &[u8], _: &mut Plain) {}
+
+/// The number of newlines in `s`.
+pub fn count_newlines(kernel: Kernel, s: &[u8]) -> usize {
+    if kernel == Scalar {
+        return s.iter().filter(|&&b| b == b'\n').count();
+    }
+    let (done, mut count) = match kernel {
+        Sse2 => newlines_sse2(s),
+        Avx2 => unsafe { newlines_avx2(s) },
+        _ => (0, 0)
+    };
+    for block in s[done..].chunks(8) {
+        if block.len() == 8 {
+            count += newlines(load(block)).count_ones() as usize;
+        } else {
+            count += block.iter().filter(|&&b| b == b'\n').count();
+        }
+    }
+    count
+}
+
+// How many bytes of whole vectors were taken, and the newlines in them
+#[cfg(target_arch = "x86_64")]
+fn newlines_sse2(s: &[u8]) -> (usize, usize) {
+    let newline = simd::splat(b'\n');
+    let mut count = 0;
+    let mut done = 0;
+    while s.len() - done >= 16 {
+        let c = simd::load(&s[done..]);
+        let mask = unsafe { _mm_movemask_epi8(_mm_cmpeq_epi8(c, newline)) };
+        count += mask.count_ones() as usize;
+        done += 16;
+    }
+    (done, count)
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn newlines_sse2(_: &[u8]) -> (usize, usize) {
+    (0, 0)
+}
+
+// Needs AVX2 and POPCNT.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,popcnt")]
+unsafe fn newlines_avx2(s: &[u8]) -> (usize, usize) {
+    let newline = _mm256_set1_epi8(b'\n' as i8);
+    let mut count = 0;
+    let mut done = 0;
+    while s.len() - done >= 32 {
+        let c = _mm256_loadu_si256(s[done..].as_ptr() as *const __m256i);
+        count += _mm256_movemask_epi8(_mm256_cmpeq_epi8(c, newline)).count_ones() as usize;
+        done += 32;
+    }
+    (done, count)
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+unsafe fn newlines_avx2(_: &[u8]) -> (usize, usize) {
+    (0, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The lines and words in plain `s`, and whether it ends in a word,
+    // a byte at a time
+    fn expected(s: &[u8], mut in_word: bool) -> (usize, usize, bool) {
+        let mut lines = 0;
+        let mut words = 0;
+        for &b in s {
+            let word = (0x21..0x7F).contains(&b);
+            if word && !in_word {
+                words += 1;
+            }
+            in_word = word;
+            if b == b'\n' {
+                lines += 1;
+            }
+        }
+        (lines, words, in_word)
+    }
+
+    // The kernels that count plain blocks
+    fn bulk_kernels() -> Vec<Kernel> {
+        kernels().into_iter().filter(|&k| k != Scalar).collect()
+    }
+
+    #[test]
+    fn plain_blocks() {
+        for b in 0..=255u8 {
+            let plain = b == b' ' || (0x09..=0x0D).contains(&b) || (0x21..0x7F).contains(&b);
+            let block = [b'a', b, b'a', b'a', b'a', b'a', b'a', b'a'];
+            assert_eq!(is_plain(load(&block)), plain);
+        }
+    }
+
+    // A byte that is not plain anywhere in a vector ends the count at the
+    // start of its block of 8, whichever loop comes upon it
+    #[test]
+    fn stops_inside_a_vector() {
+        let text = b"ab cd\nefg hij\tklm nopq\nrs tuv wxyz ab\ncd ef gh ij kl\nmn op qrst uv";
+        for &bad in &[0x00, 0x7F, 0x80, 0xC3] {
+            for pos in 0..64 {
+                let mut s = text[..64].to_vec();
+                s[pos] = bad;
+                for kernel in bulk_kernels() {
+                    let plain = count_plain(kernel, &s, false);
+                    let len = pos / 8 * 8;
+                    assert_eq!(plain.len, len, "{:?} at {}", kernel, pos);
+                    assert_eq!((plain.lines, plain.words, plain.in_word),
+                               expected(&s[..len], false), "{:?} at {}", kernel, pos);
+                }
+            }
+        }
+    }
+
+    // A word that begins in the last vector and ends in the blocks of 8
+    // after it counts once
+    #[test]
+    fn word_across_handoff() {
+        for len in 32..72 {
+            for start in 0..len {
+                for end in start + 1..=len {
+                    let mut s = vec![b' '; len];
+                    for b in s[start..end].iter_mut() {
+                        *b = b'x';
+                    }
+                    for in_word in [false, true] {
+                        for kernel in bulk_kernels() {
+                            let plain = count_plain(kernel, &s, in_word);
+                            let done = len / 8 * 8;
+                            assert_eq!(plain.len, done);
+                            assert_eq!((plain.lines, plain.words, plain.in_word),
+                                       expected(&s[..done], in_word),
+                                       "{:?}: {}..{} of {}", kernel, start, end, len);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
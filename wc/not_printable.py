@@ -0,0 +1,37 @@
+#!/usr/bin/env python3
+"""Print the NOT_PRINTABLE table of width.rs: the ranges of characters from
+U+00A0 on that the C library's iswprint() rejects in the C.UTF-8 locale.
+
+Usage: python3 wc/not_printable.py
+
+The table in width.rs was made with glibc 2.36, whose character classes
+follow Unicode 14.0.0.
+"""
+
+import ctypes
+
+LC_CTYPE = 0
+
+libc = ctypes.CDLL("libc.so.6")
+libc.setlocale.restype = ctypes.c_char_p
+if libc.setlocale(LC_CTYPE, b"C.UTF-8") is None:
+    raise SystemExit("the C.UTF-8 locale is not available")
+libc.iswprint.argtypes = [ctypes.c_uint32]
+
+ranges = []
+start = None
+for c in range(0xA0, 0x110000):
+    if not libc.iswprint(c):
+        if start is None:
+            start = c
+    elif start is not None:
+        ranges.append((start, c - 1))
+        start = None
+if start is not None:
+    ranges.append((start, 0x10FFFF))
+
+pairs = ["(0x%04X, 0x%04X)" % r for r in ranges]
+print("static NOT_PRINTABLE: &[(u32, u32)] = &[")
+lines = [", ".join(pairs[i:i + 4]) for i in range(0, len(pairs), 4)]
+print(",\n".join("    " + line for line in lines))
+print("];")
@@ -1,41 +1,141 @@
-extern crate getopts;
-use getopts::{optflag,getopts,OptGroup};
-use std::{io,os};
-
-fn print_usage(program: &String, opts: &[OptGroup]) {
-    let desc = format!(
-        "Usage:\t{} [OPTION]... [FILE]...",
-            program);
+use common::{Command, print_error, print_file_error, usage_error, optflag, optopt};
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::Add;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+mod fast;
+mod width;
+
+static PROGRAM: &str = "wc";
+
+pub fn command() -> Command {
+    Command::new(PROGRAM,
+        &["[OPTION]... [FILE]...", "[OPTION]... --files0-from=F"],
+        "Print newline, word, and byte counts for each FILE, and a total line if \
+        more than one FILE is specified.",
+        "With no FILE, or when FILE is -, read standard input.",
+        &[
+            optflag("l", "lines", "print the newline counts"),
+            optflag("w", "words", "print the word counts"),
+            optflag("m", "chars", "print the character counts"),
+            optflag("c", "bytes", "print the byte counts"),
+            optflag("L", "max-length", "print the maximum display width"),
+            optopt("", "files0-from", "read input from the files specified by NUL-terminated\n\
+                                       names in file F; if F is - then read names from\n\
+                                       standard input", "F"),
+            optopt("", "total", "when to print a line with total counts;\n\
+                                 WHEN can be: auto, always, only, never", "WHEN"),
+            optopt("", "output-format", "print the counts as FORMAT: columns (the default),\n\
+                                         json (an object per line) or csv", "FORMAT")
+        ])
+}
 
-    println!("{}", getopts::usage(desc.as_slice(), opts));
+#[derive(Clone, Copy)]
+struct WcInfo {
+    lines: Option<usize>,
+    words: Option<usize>,
+    chars: Option<usize>,
+    bytes: Option<usize>,
+    max_l: Option<usize>
 }
 
-fn print_file_error(filename: &str) {
-    println!("wc: {}: No such file or directory", filename);
-    os::set_exit_status(1);
+impl WcInfo {
+    // The requested counts right-aligned in columns of `width`, as GNU wc
+    // prints them
+    fn columns(&self, width: usize) -> String {
+        let mut out = String::new();
+        let counts = [self.lines, self.words, self.chars, self.bytes, self.max_l];
+        for n in counts.iter().filter_map(|c| *c) {
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            let digits = format!("{}", n);
+            for _ in digits.len()..width {
+                out.push(' ');
+            }
+            out.push_str(&digits);
+        }
+        out
+    }
+
+    // The requested counts with the names of their fields
+    fn fields(&self) -> Vec<(&'static str, usize)> {
+        let counts = [self.lines, self.words, self.chars, self.bytes, self.max_l];
+        FIELD_NAMES.iter().zip(counts.iter())
+            .filter_map(|(name, count)| count.map(|n| (*name, n)))
+            .collect()
+    }
+
+    // A JSON object of the counts of file `name`, or of the total if it is
+    // None
+    fn json(&self, name: Option<&str>) -> String {
+        let mut out = String::from("{");
+        for &(field, n) in self.fields().iter() {
+            out.push_str(&format!("\"{}\":{},", field, n));
+        }
+        out.push_str("\"filename\":");
+        match name {
+            Some(name) => out.push_str(&json_string(name)),
+            None => out.push_str("null")
+        }
+        out.push('}');
+        out
+    }
+
+    // A CSV record of the counts of file `name`, or of the total, with an
+    // empty name, if it is None
+    fn csv(&self, name: Option<&str>) -> String {
+        let mut out = String::new();
+        for &(_, n) in self.fields().iter() {
+            out.push_str(&format!("{},", n));
+        }
+        out.push_str(&csv_field(name.unwrap_or("")));
+        out
+    }
 }
 
-struct WcInfo {
-    lines: Option<uint>,
-    words: Option<uint>,
-    chars: Option<uint>,
-    bytes: Option<uint>,
-    max_l: Option<uint>
+// The fields of the JSON and CSV output, in the order of the columns
+static FIELD_NAMES: [&str; 5] = ["lines", "words", "chars", "bytes", "max_line_length"];
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                out.push_str("\\u");
+                out.push_str(&format!("{:04x}", c as u32));
+            }
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    out
 }
 
-impl std::fmt::Show for WcInfo {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}{}{}{}{}",
-               option_str(self.lines),
-               option_str(self.words),
-               option_str(self.chars),
-               option_str(self.bytes),
-               option_str(self.max_l))
+// A field quoted as RFC 4180 has it when it holds a comma, a quote or a
+// line break
+fn csv_field(s: &str) -> String {
+    if !s.chars().any(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        return String::from(s);
     }
+    format!("\"{}\"", s.replace('"', "\"\""))
 }
 
-impl Add<WcInfo, WcInfo> for WcInfo {
-    fn add(&self, rhs: &WcInfo) -> WcInfo {
+impl Add for WcInfo {
+    type Output = WcInfo;
+
+    fn add(self, rhs: WcInfo) -> WcInfo {
         WcInfo {
             lines: option_sum(self.lines, rhs.lines),
             words: option_sum(self.words, rhs.words),
@@ -43,223 +143,878 @@ impl Add<WcInfo, WcInfo> for WcInfo {
             bytes: option_sum(self.bytes, rhs.bytes),
             max_l: option_max(self.max_l, rhs.max_l),
         }
-  }
+    }
+}
+
+// When to print the line of totals
+#[derive(Clone, Copy)]
+enum Total {
+    // When there is more than one file
+    Auto,
+    Always,
+    // Instead of the counts of each file, and without the word "total"
+    Only,
+    Never
 }
 
+static TOTAL_WHEN: [(&str, Total); 4] = [
+    ("auto", Total::Auto),
+    ("always", Total::Always),
+    ("only", Total::Only),
+    ("never", Total::Never)
+];
+
+// How the counts are printed
+#[derive(Clone, Copy)]
+enum Format {
+    // Aligned, as GNU wc prints them
+    Columns,
+    // A JSON object per line
+    Json,
+    // CSV with a header line
+    Csv
+}
+
+use self::Format::*;
+
+static FORMATS: [(&str, Format); 3] = [
+    ("columns", Columns),
+    ("json", Json),
+    ("csv", Csv)
+];
+
+#[derive(Clone, Copy)]
 struct Config {
     lines: bool,
     words: bool,
     chars: bool,
     bytes: bool,
-    max_l: bool
+    max_l: bool,
+    total: Total,
+    format: Format,
+    // Whether the locale is UTF-8, and if so whether non-breaking spaces
+    // separate words
+    utf8: bool,
+    nbsp: bool,
+    // How blocks of plain ASCII are counted
+    kernel: fast::Kernel
 }
 
-fn get_lines(s: &String) -> uint {
-    let mut newlines = 0;
-    for c in s.as_slice().chars() {
-        if c == '\n' { newlines += 1; }
-    };
-    newlines
+// The choice named by `arg`, the argument of option `opt`, which may be
+// abbreviated as long as it stays unambiguous. Reports a bad one as GNU's
+// argmatch does.
+fn argmatch<T: Copy>(opt: &str, arg: &str, choices: &[(&'static str, T)]) -> Option<T> {
+    let mut found = None;
+    let mut ambiguous = false;
+    for &(name, choice) in choices.iter() {
+        if name == arg {
+            return Some(choice);
+        }
+        if name.starts_with(arg) {
+            ambiguous = found.is_some();
+            found = Some(choice);
+        }
+    }
+    if found.is_some() && !ambiguous {
+        return found;
+    }
+
+    let mut msg = format!("{} argument '{}' for '--{}'\nValid arguments are:",
+                          if ambiguous { "ambiguous" } else { "invalid" }, arg, opt);
+    for &(name, _) in choices.iter() {
+        msg.push_str(&format!("\n  - '{}'", name));
+    }
+    usage_error(PROGRAM, &msg);
+    None
 }
 
-fn is_whitespace(c: char) -> bool {
-    match c {
-        '\n' | '\t' | ' ' => true,
-        _ => {
-            let c_ascii = c as uint;
-            (c_ascii >= 11 && c_ascii <= 13)
-        }
+// The next NUL-terminated name in a list of names, or None at its end
+fn read_name(reader: &mut dyn BufRead) -> io::Result<Option<String>> {
+    let mut name = Vec::new();
+    if reader.read_until(0, &mut name)? == 0 {
+        return Ok(None);
+    }
+    if name.last() == Some(&0) {
+        name.pop();
     }
+    Ok(Some(String::from_utf8_lossy(&name).into_owned()))
 }
 
-fn get_words(s: &String) -> uint {
-    let mut words = 0;
-    let mut has_words = false;
-    for c in s.as_slice().chars() {
-        let cur_ws = is_whitespace(c);
+// All the names left in a list of names
+fn read_names(reader: &mut dyn BufRead) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    while let Some(name) = read_name(reader)? {
+        names.push(name);
+    }
+    Ok(names)
+}
 
-        if has_words && cur_ws {
-            words += 1;
-        }
-        has_words = !cur_ws;
+// Whether the list of names `list` is a regular file. GNU wc only reads
+// such a list ahead of time, to size the columns; others are read a name
+// at a time as the files are counted.
+fn is_regular_list(list: &str) -> bool {
+    let path = if list == "-" { "/dev/stdin" } else { list };
+    match fs::metadata(path) {
+        Ok(ref st) => st.is_file(),
+        Err(_) => false
     }
-    words
 }
 
-fn get_chars(s: &String) -> uint {
-    s.as_slice().char_len()
+// Whitespace in a UTF-8 locale, as iswspace() has it, and also the
+// non-breaking spaces unless `nbsp` is false
+fn is_space(c: u32, nbsp: bool) -> bool {
+    match c {
+        0x09..=0x0D | 0x20 | 0x1680 | 0x2000..=0x2006 | 0x2008..=0x200A |
+        0x2028 | 0x2029 | 0x205F | 0x3000 => true,
+        0xA0 | 0x2007 | 0x202F | 0x2060 => nbsp,
+        _ => false
+    }
 }
 
-fn get_bytes(s: &String) -> uint {
-    s.clone().into_bytes().len()
+// Every counter, taken in one pass over each chunk of the input
+struct Counter {
+    lines: usize,
+    words: usize,
+    chars: usize,
+    bytes: usize,
+    max_l: usize,
+    // Whether input is decoded as UTF-8 or taken a byte at a time, and
+    // whether non-breaking spaces separate words
+    utf8: bool,
+    nbsp: bool,
+    // Whether the last chunk ended inside a word, and the display column
+    // it ended at
+    in_word: bool,
+    column: usize,
+    // The bits so far of a UTF-8 character split across chunks, how many
+    // more bytes it needs, and its smallest valid value
+    partial: u32,
+    needed: usize,
+    min: u32,
+    // How blocks of plain ASCII are counted. They are counted a byte at a
+    // time when -L is wanted, since that follows every column.
+    kernel: fast::Kernel
 }
 
-fn get_max_l(s: &String) -> uint {
-    let mut max_l: uint = 0;
-    let mut cur_length = 0;
-    for c in s.as_slice().chars() {
-        if c != '\n' {
-            cur_length += 1;
-            max_l = std::cmp::max(cur_length, max_l);
+impl Counter {
+    fn new(conf: Config) -> Counter {
+        Counter {
+            lines: 0, words: 0, chars: 0, bytes: 0, max_l: 0,
+            utf8: conf.utf8, nbsp: conf.nbsp,
+            in_word: false, column: 0, partial: 0, needed: 0, min: 0,
+            kernel: if conf.max_l { fast::Kernel::Scalar } else { conf.kernel }
+        }
+    }
+
+    fn advance(&mut self, width: usize) {
+        self.column += width;
+        if self.column > self.max_l {
+            self.max_l = self.column;
+        }
+    }
+
+    // Count character `c`, as GNU wc does. Tabs move to the next multiple
+    // of 8 columns, and carriage returns and form feeds go back to the
+    // start of the line like newlines. Other characters take their display
+    // width. Characters that are not printable neither start nor end a
+    // word.
+    fn update_char(&mut self, c: u32) {
+        self.chars += 1;
+        match c {
+            0x0A | 0x0C | 0x0D => {
+                self.column = 0;
+                self.in_word = false;
+            }
+            0x09 => {
+                let width = 8 - self.column % 8;
+                self.advance(width);
+                self.in_word = false;
+            }
+            0x0B => self.in_word = false,
+            _ => {
+                let printable = if self.utf8 { width::is_printable(c) } else { (0x20..0x7F).contains(&c) };
+                if !printable {
+                    return;
+                }
+                self.advance(width::width(c));
+                if self.utf8 && is_space(c, self.nbsp) || c == 0x20 {
+                    self.in_word = false;
+                } else if !self.in_word {
+                    self.words += 1;
+                    self.in_word = true;
+                }
+            }
+        }
+    }
+
+    // Decode byte `b` as part of UTF-8. Bytes that are not part of a valid
+    // sequence are not characters at all.
+    fn update_utf8(&mut self, b: u8) {
+        if b < 0x80 {
+            self.needed = 0;
+            self.update_char(b as u32);
+        } else if b & 0xC0 == 0x80 {
+            if self.needed > 0 {
+                self.partial = (self.partial << 6) | (b & 0x3F) as u32;
+                self.needed -= 1;
+                if self.needed == 0 {
+                    let c = self.partial;
+                    if (self.min..=0x10FFFF).contains(&c) && !(0xD800..0xE000).contains(&c) {
+                        self.update_char(c);
+                    }
+                }
+            }
+        } else {
+            let (needed, bits, min) = match b {
+                0xC2..=0xDF => (1, b & 0x1F, 0x80),
+                0xE0..=0xEF => (2, b & 0x0F, 0x800),
+                0xF0..=0xF4 => (3, b & 0x07, 0x10000),
+                _ => (0, 0, 0)
+            };
+            self.needed = needed;
+            self.partial = bits as u32;
+            self.min = min;
+        }
+    }
+
+    fn update_byte(&mut self, b: u8) {
+        if b == b'\n' {
+            self.lines += 1;
+        }
+        if self.utf8 {
+            self.update_utf8(b);
         } else {
-            cur_length = 0;
+            self.update_char(b as u32);
+        }
+    }
+
+    // Blocks of printable ASCII and ASCII whitespace, which count the same
+    // in every locale: each byte is a character, and either part of a word
+    // or a separator
+    fn update_plain(&mut self, plain: fast::Plain) {
+        if plain.len == 0 {
+            return;
+        }
+        self.lines += plain.lines;
+        self.words += plain.words;
+        self.in_word = plain.in_word;
+        self.chars += plain.len;
+        self.needed = 0;
+    }
+
+    // Only newlines and bytes are wanted
+    fn update_lines(&mut self, s: &[u8]) {
+        self.lines += fast::count_newlines(self.kernel, s);
+        self.bytes += s.len();
+    }
+
+    fn update(&mut self, s: &[u8]) {
+        self.bytes += s.len();
+        let mut rest = s;
+        if self.kernel != fast::Kernel::Scalar {
+            loop {
+                let plain = fast::count_plain(self.kernel, rest, self.in_word);
+                rest = &rest[plain.len..];
+                self.update_plain(plain);
+                if rest.len() < 8 {
+                    break;
+                }
+                // A block that is not plain
+                for &b in &rest[..8] {
+                    self.update_byte(b);
+                }
+                rest = &rest[8..];
+            }
+        }
+        for &b in rest {
+            self.update_byte(b);
         }
     }
-    max_l as uint
 }
 
-fn option_sum(a: Option<uint>, b: Option<uint>) -> Option<uint> {
+fn option_sum(a: Option<usize>, b: Option<usize>) -> Option<usize> {
     match (a, b) {
         (Some(i1), Some(i2)) => Some(i1 + i2),
         _ => None
     }
 }
 
-fn option_max(a: Option<uint>, b: Option<uint>) -> Option<uint> {
+fn option_max(a: Option<usize>, b: Option<usize>) -> Option<usize> {
     match (a, b) {
         (Some(i1), Some(i2)) => Some(std::cmp::max(i1, i2)),
         _ => None
     }
 }
 
-fn option_str(a: Option<uint>) -> String {
-    match a {
-        Some(i) => format!("\t{}", i),
-        _ => String::from_str("")
+// What counting a file, or a piece of one, came to: its counts unless it
+// could not be opened, and the error that stopped it, if any. The counts
+// up to a read error are still printed, as GNU wc prints them.
+struct Outcome {
+    wc: Option<WcInfo>,
+    error: Option<io::Error>
+}
+
+impl Outcome {
+    // The outcome of two pieces of the same file
+    fn merge(self, rhs: Outcome) -> Outcome {
+        let wc = match (self.wc, rhs.wc) {
+            (Some(a), Some(b)) => Some(a + b),
+            _ => None
+        };
+        Outcome { wc, error: self.error.or(rhs.error) }
     }
 }
 
-fn get_wc(s: &String, conf: Config) -> WcInfo {
-    let lines = if conf.lines { Some(get_lines(s)) } else { None };
-    let words = if conf.words { Some(get_words(s)) } else { None };
-    let chars = if conf.chars { Some(get_chars(s)) } else { None };
-    let bytes = if conf.bytes { Some(get_bytes(s)) } else { None };
-    let max_l = if conf.max_l { Some(get_max_l(s)) } else { None };
+fn get_wc(reader: &mut dyn Read, conf: Config) -> Outcome {
+    let mut counter = Counter::new(conf);
+    let lines_only = !(conf.words || conf.chars || conf.max_l);
+    let result = common::for_each_chunk(reader, |chunk| {
+        if lines_only {
+            counter.update_lines(chunk);
+        } else {
+            counter.update(chunk);
+        }
+        Ok(())
+    });
+
+    let wc = WcInfo {
+        lines: if conf.lines { Some(counter.lines) } else { None },
+        words: if conf.words { Some(counter.words) } else { None },
+        chars: if conf.chars { Some(counter.chars) } else { None },
+        bytes: if conf.bytes { Some(counter.bytes) } else { None },
+        max_l: if conf.max_l { Some(counter.max_l) } else { None }
+    };
+    Outcome { wc: Some(wc), error: result.err() }
+}
+
+fn bytes_only(conf: Config) -> bool {
+    conf.bytes && !(conf.lines || conf.words || conf.chars || conf.max_l)
+}
 
-    WcInfo {
-        lines: lines,
-        words: words,
-        chars: chars,
-        bytes: bytes,
-        max_l: max_l
+// The size of a regular file, when that is all that is wanted of it, taken
+// from the open file. Other files, and those that report no size (as in
+// /proc), have to be read.
+fn size_only(file: &mut File, conf: Config) -> Option<WcInfo> {
+    if !bytes_only(conf) {
+        return None;
+    }
+    match file.metadata() {
+        Ok(ref st) if st.is_file() && st.len() > 0 => Some(WcInfo {
+            lines: None,
+            words: None,
+            chars: None,
+            bytes: Some(st.len() as usize),
+            max_l: None
+        }),
+        _ => None
     }
 }
 
-fn do_wc(strings: Vec<(String,&String)>, conf: Config) {
-    let mut num = 0;
+// The file is opened even when its size will do, so that it has to be
+// readable, as when it is read
+fn count_file(filename: &str, conf: Config) -> Outcome {
+    if filename == "-" {
+        return get_wc(&mut io::stdin(), conf);
+    }
+    match File::open(filename) {
+        Ok(mut file) => match size_only(&mut file, conf) {
+            Some(wc) => Outcome { wc: Some(wc), error: None },
+            None => get_wc(&mut file, conf)
+        },
+        Err(e) => Outcome { wc: None, error: Some(e) }
+    }
+}
 
-    let mut sum = WcInfo {
-        lines: Some(0),
-        words: Some(0),
-        chars: Some(0),
-        bytes: Some(0),
-        max_l: Some(0)
+// Count the bytes of a file from `start` up to `end`, or to the end of the
+// file
+fn count_piece(filename: &str, start: u64, end: Option<u64>, conf: Config) -> Outcome {
+    let mut file = match File::open(filename) {
+        Ok(f) => f,
+        Err(e) => return Outcome { wc: None, error: Some(e) }
     };
-    for tup in strings.iter() {
-        let (ref s, name) = *tup;
-        let suffix =
-            if name.as_slice() != "" {
-                format!("\t{}", name)
-            } else {
-                String::from_str("")
+    if let Err(e) = file.seek(SeekFrom::Start(start)) {
+        return Outcome { wc: None, error: Some(e) };
+    }
+    match end {
+        Some(end) => get_wc(&mut file.take(end - start), conf),
+        None => get_wc(&mut file, conf)
+    }
+}
+
+// Regular files at least this big are cut into pieces counted in parallel
+const SPLIT_SIZE: u64 = 16 * 1024 * 1024;
+
+// Where to cut the file `filename` into about `parts` pieces: each piece
+// but the first starts just after a newline, where the counter starts
+// afresh, so no word, character or line is cut in two and the counts of
+// the pieces simply add up. Small files and those that are not regular
+// are not cut, and give no pieces.
+fn pieces(filename: &str, parts: usize) -> Vec<(u64, Option<u64>)> {
+    let size = match fs::metadata(filename) {
+        Ok(ref st) if st.is_file() && st.len() >= SPLIT_SIZE => st.len(),
+        _ => return Vec::new()
+    };
+    let mut file = match File::open(filename) {
+        Ok(f) => f,
+        Err(_) => return Vec::new()
+    };
+
+    let mut starts = vec![0u64];
+    let mut buf = [0u8; 4096];
+    for k in 1..parts {
+        let last = starts[starts.len() - 1];
+        let mut pos = std::cmp::max(size / parts as u64 * k as u64, last);
+        if file.seek(SeekFrom::Start(pos)).is_err() {
+            break;
+        }
+        let mut found = false;
+        while !found {
+            let n = match file.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n
             };
-        let wc = get_wc(s, conf);
-        println!("{}{}", wc, suffix);
+            match buf[..n].iter().position(|&b| b == b'\n') {
+                Some(i) => {
+                    pos += i as u64 + 1;
+                    found = true;
+                }
+                None => pos += n as u64
+            }
+        }
+        if !found || pos >= size {
+            break;
+        }
+        starts.push(pos);
+    }
+
+    if starts.len() == 1 {
+        return Vec::new();
+    }
+    (0..starts.len()).map(|i| (starts[i], starts.get(i + 1).copied())).collect()
+}
+
+// A file, or a piece of one, for a worker to count
+struct Job {
+    // The position of the file among the names
+    index: usize,
+    name: String,
+    // The piece of the file, or None for all of it
+    piece: Option<(u64, Option<u64>)>,
+    // How many pieces the file was cut into
+    parts: usize
+}
+
+// Count the named files in `names` on `threads` threads, big ones in
+// pieces. Each outcome comes back with the index of its file among the
+// names and the number of pieces that file was cut into. The files are cut
+// up on a thread of their own, which hands each job over as soon as it is
+// made, so counting starts with the first file while later ones are still
+// being cut.
+fn start_workers(names: &[String], threads: usize, conf: Config)
+        -> Receiver<(usize, usize, Outcome)> {
+    let (tx, rx) = channel();
+    if !names.iter().any(|name| !name.is_empty() && name != "-") {
+        return rx;
+    }
+
+    let (job_tx, job_rx) = channel::<Job>();
+    let job_rx = Arc::new(Mutex::new(job_rx));
+    for _ in 0..threads {
+        let tx = tx.clone();
+        let jobs = job_rx.clone();
+        thread::spawn(move || {
+            loop {
+                // The lock is only held while waiting for the next job
+                let job = match jobs.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => break
+                };
+                let outcome = match job.piece {
+                    Some((start, end)) => count_piece(&job.name, start, end, conf),
+                    None => count_file(&job.name, conf)
+                };
+                if tx.send((job.index, job.parts, outcome)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    let names = names.to_vec();
+    thread::spawn(move || {
+        for (i, name) in names.into_iter().enumerate() {
+            if name.is_empty() || name == "-" {
+                continue;
+            }
+            let split =
+                if threads > 1 && !bytes_only(conf) {
+                    pieces(&name, threads)
+                } else {
+                    Vec::new()
+                };
+            let sent =
+                if split.is_empty() {
+                    job_tx.send(Job { index: i, name, piece: None, parts: 1 }).is_ok()
+                } else {
+                    let parts = split.len();
+                    split.into_iter().all(|piece| {
+                        let job = Job { index: i, name: name.clone(), piece: Some(piece), parts };
+                        job_tx.send(job).is_ok()
+                    })
+                };
+            if !sent {
+                break;
+            }
+        }
+    });
+    rx
+}
 
-        sum = wc + sum;
-        num += 1;
+fn num_counters(conf: Config) -> usize {
+    [conf.lines, conf.words, conf.chars, conf.bytes, conf.max_l].iter()
+        .filter(|b| **b).count()
+}
+
+// The width of each column: enough for the digits of the total size of the
+// files, or at least 7 if any of them is not a regular file, whose size
+// says nothing. A single count for a single file is not padded at all.
+fn number_width(names: &[String], conf: Config, files0_from: Option<&str>) -> usize {
+    if names.len() == 1 && num_counters(conf) == 1 {
+        return 1;
     }
-    if num > 1 {
-        println!("{}\t{}", sum, "total");
+
+    let mut min_width = 1;
+    let mut total = 0u64;
+    for name in names.iter() {
+        if name.is_empty() && files0_from.is_some() {
+            continue;
+        }
+        let path = if name.is_empty() || name == "-" { "/dev/stdin" } else { name };
+        match fs::metadata(path) {
+            Ok(ref st) if st.is_file() => total += st.len(),
+            Ok(_) => min_width = 7,
+            Err(_) => {}
+        }
     }
+    std::cmp::max(format!("{}", total).len(), min_width)
 }
 
-fn read_stdin() -> String {
-    let mut s = String::from_str("");
-    for line in io::stdin().lines() {
-        s.push_str(line.unwrap().as_slice());
+// Whether the name at position `n` (from 1) of the list `files0_from`, if
+// the names come from one, names a file. Those that do not are reported.
+fn check_name(name: &str, n: usize, files0_from: Option<&str>) -> bool {
+    match files0_from {
+        Some(list) if name.is_empty() => {
+            let msg = format!("{}:{}: invalid zero-length file name", list, n);
+            print_error(PROGRAM, &msg);
+            false
+        }
+        Some("-") if name == "-" => {
+            print_error(PROGRAM, "when reading file names from stdin, no file name of '-' allowed");
+            false
+        }
+        _ => true
     }
-    return s;
 }
 
-#[deriving(Show)]
-enum FileErr { FileNotFound }
-fn read_file(filename: &String) -> Result<String, FileErr> {
-    let path = Path::new(filename.as_slice());
-    let mut file = std::io::File::open(&path);
-    let temp = file.read_to_end();
-    match temp {
-        Ok(s) => {
-            let contents = std::str::from_utf8_owned(s);
-            Ok(contents.unwrap())
-        },
-        Err(_) => {
-            Err(FileNotFound)
+fn print_header(conf: Config) {
+    if let Csv = conf.format {
+        let header = [conf.lines, conf.words, conf.chars, conf.bytes, conf.max_l];
+        for (name, _) in FIELD_NAMES.iter().zip(header.iter()).filter(|&(_, wanted)| *wanted) {
+            print!("{},", name);
+        }
+        println!("filename");
+    }
+}
+
+// Print the counts of the file `name`, or why it could not be counted, and
+// add them to `sum`. An empty name means standard input, printed without
+// a name.
+fn print_counts(name: &str, outcome: Outcome, conf: Config, width: usize, sum: &mut WcInfo) {
+    let filename = if !name.is_empty() { name } else { "-" };
+    let suffix = if !name.is_empty() { format!(" {}", name) } else { String::new() };
+
+    // A file that cannot be opened is reported and left out, and the
+    // others are still counted
+    if let Some(ref e) = outcome.error {
+        print_file_error(PROGRAM, filename, e);
+    }
+    let wc = match outcome.wc {
+        Some(wc) => wc,
+        None => return
+    };
+    match (conf.total, conf.format) {
+        (Total::Only, _) => {},
+        (_, Columns) => println!("{}{}", wc.columns(width), suffix),
+        (_, Json) => println!("{}", wc.json(Some(filename))),
+        (_, Csv) => println!("{}", wc.csv(Some(filename)))
+    }
+    *sum = wc + *sum;
+}
+
+// The total of `nfiles` files, if it is wanted
+fn print_total(sum: WcInfo, nfiles: usize, conf: Config, width: usize) {
+    let print_total = match conf.total {
+        Total::Auto => nfiles > 1,
+        Total::Always | Total::Only => true,
+        Total::Never => false
+    };
+    if print_total {
+        match (conf.total, conf.format) {
+            (Total::Only, Columns) => println!("{}", sum.columns(width)),
+            (_, Columns) => println!("{} total", sum.columns(width)),
+            (_, Json) => println!("{}", sum.json(None)),
+            (_, Csv) => println!("{}", sum.csv(None))
         }
     }
 }
 
-fn main() {
-    let args = os::args();
-    let program = args.get(0).clone();
+static ZERO: WcInfo = WcInfo {
+    lines: Some(0),
+    words: Some(0),
+    chars: Some(0),
+    bytes: Some(0),
+    max_l: Some(0)
+};
+
+// Count the files named in `names`, which were read from the list
+// `files0_from` if given. An empty name means standard input.
+fn do_wc(names: &[String], conf: Config, files0_from: Option<&str>) {
+    let width = match conf.total {
+        Total::Only => 1,
+        _ => number_width(names, conf, files0_from)
+    };
 
-    let opts = [
-        optflag("l", "lines", "print the newline counts"),
-        optflag("w", "words", "print the word counts"),
-        optflag("m", "chars", "print the character counts"),
-        optflag("c", "bytes", "print the byte counts"),
-        optflag("L", "max-length", "print the length of the longest line"),
-        optflag("h", "help", "display this help and exit")
-    ];
-    let matches = match getopts(args.tail(), opts) {
-        Ok(m) => { m }
-        Err(f) => { fail!(f) }
+    // Named files are counted by workers, big ones in pieces, while
+    // standard input is read here when its turn comes. A file's outcome is
+    // complete once all its pieces are back; how many there are is only
+    // known once the first one is.
+    let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let results = start_workers(names, threads, conf);
+    let mut outcomes: Vec<Option<Outcome>> = (0..names.len()).map(|_| None).collect();
+    let mut received = vec![0usize; names.len()];
+    let mut parts = vec![0usize; names.len()];
+
+    let mut sum = ZERO;
+    print_header(conf);
+    for (i, name) in names.iter().enumerate() {
+        if !check_name(name, i + 1, files0_from) {
+            continue;
+        }
+
+        let outcome =
+            if name.is_empty() || name == "-" {
+                count_file("-", conf)
+            } else {
+                // Results come back in any order; those of later files wait
+                while parts[i] == 0 || received[i] < parts[i] {
+                    let (j, n, outcome) = results.recv().unwrap();
+                    let merged = match outcomes[j].take() {
+                        Some(prev) => prev.merge(outcome),
+                        None => outcome
+                    };
+                    outcomes[j] = Some(merged);
+                    received[j] += 1;
+                    parts[j] = n;
+                }
+                outcomes[i].take().unwrap()
+            };
+        print_counts(name, outcome, conf, width, &mut sum);
+    }
+    print_total(sum, names.len(), conf, width);
+}
+
+// Count the files named in the list `list` as their names are read from
+// `reader`, one at a time. Nothing is known of the files up front, so the
+// columns are not padded.
+fn do_wc_stream(reader: &mut dyn BufRead, conf: Config, list: &str) {
+    let mut sum = ZERO;
+    let mut n = 0;
+    print_header(conf);
+    loop {
+        let name = match read_name(reader) {
+            Ok(Some(name)) => name,
+            Ok(None) => break,
+            Err(e) => {
+                let msg = format!("{}: read error: {}", common::quote_name(list),
+                                  common::strerror(&e));
+                print_error(PROGRAM, &msg);
+                break;
+            }
+        };
+        n += 1;
+        if !check_name(&name, n, Some(list)) {
+            continue;
+        }
+        let outcome = count_file(&name, conf);
+        print_counts(&name, outcome, conf, 1, &mut sum);
+    }
+    print_total(sum, n, conf, 1);
+}
+
+pub fn uumain(args: Vec<String>) {
+    let matches = match command().parse(&args[1..]) {
+        Some(m) => m,
+        None => return
     };
 
     let opt_l = matches.opt_present("l");
     let opt_w = matches.opt_present("w");
     let opt_m = matches.opt_present("m");
     let opt_c = matches.opt_present("c");
-    let opt_L = matches.opt_present("L");
+    let opt_max_l = matches.opt_present("L");
+
+    let total = match matches.opt_str("total") {
+        Some(s) => match argmatch("total", &s, &TOTAL_WHEN) {
+            Some(t) => t,
+            None => return
+        },
+        None => Total::Auto
+    };
+    let format = match matches.opt_str("output-format") {
+        Some(s) => match argmatch("output-format", &s, &FORMATS) {
+            Some(f) => f,
+            None => return
+        },
+        None => Columns
+    };
 
     // If no flags, then go with default (-l -w -c)
-    let noflags = !(opt_l || opt_w || opt_m || opt_c || opt_L);
+    let noflags = !(opt_l || opt_w || opt_m || opt_c || opt_max_l);
     let config = Config {
         lines: if noflags { true } else { opt_l },
         words: if noflags { true } else { opt_w },
         chars: opt_m,
         bytes: if noflags { true } else { opt_c },
-        max_l: opt_L
+        max_l: opt_max_l,
+        total,
+        format,
+        utf8: common::utf8_locale(),
+        // GNU wc takes them as spaces, but POSIX does not
+        nbsp: env::var_os("POSIXLY_CORRECT").is_none(),
+        kernel: fast::best()
     };
 
-    let help = matches.opt_present("h");
-    let free = matches.free.clone();
-    let len = free.len();
+    let list = match matches.opt_str("files0-from") {
+        Some(list) => list,
+        None => {
+            let names =
+                if matches.free.is_empty() {
+                    vec![String::new()]
+                } else {
+                    matches.free.clone()
+                };
+            do_wc(&names, config, None);
+            return;
+        }
+    };
 
-    if help {
-        print_usage(&program, opts);
+    if !matches.free.is_empty() {
+        let msg = format!("extra operand '{}'\n\
+                           file operands cannot be combined with --files0-from",
+                          matches.free[0]);
+        usage_error(PROGRAM, &msg);
+        return;
+    }
+    let mut reader = match common::open_input(&list) {
+        Ok(r) => BufReader::new(r),
+        Err(e) => {
+            let msg = format!("cannot open {} for reading: {}", common::quote_name_always(&list),
+                              common::strerror(&e));
+            print_error(PROGRAM, &msg);
+            return;
+        }
+    };
+    if !is_regular_list(&list) {
+        do_wc_stream(&mut reader, config, &list);
         return;
     }
+    match read_names(&mut reader) {
+        Ok(names) => do_wc(&names, config, Some(&list)),
+        Err(e) => {
+            let msg = format!("{}: read error: {}", common::quote_name(&list),
+                              common::strerror(&e));
+            print_error(PROGRAM, &msg);
+        }
+    }
+}
 
-    let s;
-    let blank = String::from_str("");
-    let strings: Vec<(String, &String)> =
-        if len == 0 {
-            s = read_stdin();
-            vec![(s, &blank)]
-        } else {
-            // Read from files
-            let mut v = vec![];
-            for filename in free.iter() {
-                let temp = read_file(filename);
-                if !temp.is_ok() {
-                    print_file_error(filename.as_slice());
-                    return;
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+    use rand::seq::SliceRandom;
+    use super::{Config, Counter, Format, Total};
+    use crate::fast;
+
+    // The scalar counter is checked against GNU wc by the conformance
+    // tests; these check that the bulk path agrees with it, with each
+    // kernel the processor can run
+
+    fn config(utf8: bool, nbsp: bool, kernel: fast::Kernel) -> Config {
+        Config {
+            lines: true, words: true, chars: true, bytes: true, max_l: false,
+            total: Total::Auto, format: Format::Columns, utf8, nbsp, kernel
+        }
+    }
+
+    // Text that is mostly plain ASCII, with runs of everything that is not.
+    // The long piece fills whole vectors.
+    static PIECES: &[&[u8]] = &[
+        b"a", b"word", b"longer words", b" ", b"  ", b"\n", b"\t", b"\x0b", b"\r\n",
+        b"a plain run of words,\tspaces and\nnewlines that is longer than a vector ",
+        b"\x00", b"\x01", b"\x7f", b"\xc3\xa9", b"\xe3\x80\x80", b"\xc2\xa0",
+        b"\xf0\x9f\x98\x80", b"\xff", b"\xe3\x80", b"\x80"
+    ];
+
+    fn random_text<R: Rng>(rng: &mut R, max: usize) -> Vec<u8> {
+        let mut text = Vec::new();
+        for _ in 0..rng.gen_range(0..max) {
+            if rng.gen_bool(0.1) {
+                text.push(rng.gen::<u8>());
+            } else {
+                text.extend_from_slice(PIECES.choose(rng).unwrap());
+            }
+        }
+        text
+    }
+
+    // Count `text` fed in random pieces
+    fn count<R: Rng>(rng: &mut R, text: &[u8], conf: Config) -> Counter {
+        let mut counter = Counter::new(conf);
+        let mut rest = text;
+        while !rest.is_empty() {
+            let n = rng.gen_range(1..rest.len() + 1);
+            counter.update(&rest[..n]);
+            rest = &rest[n..];
+        }
+        counter
+    }
+
+    #[test]
+    fn bulk_counts_match_scalar() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..2000 {
+            let text = random_text(&mut rng, 200);
+            for (utf8, nbsp) in [(false, false), (true, false), (true, true)] {
+                let scalar = count(&mut rng, &text, config(utf8, nbsp, fast::Kernel::Scalar));
+                for kernel in fast::kernels() {
+                    let bulk = count(&mut rng, &text, config(utf8, nbsp, kernel));
+                    assert_eq!((bulk.lines, bulk.words, bulk.chars, bulk.bytes, bulk.in_word),
+                               (scalar.lines, scalar.words, scalar.chars, scalar.bytes,
+                                scalar.in_word));
                 }
-                v.push((temp.unwrap(), filename));
             }
-            v
-        };
+        }
+    }
 
-    do_wc(strings, config);
+    #[test]
+    fn newline_count_matches_scalar() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..2000 {
+            let text = random_text(&mut rng, 200);
+            let expected = text.iter().filter(|&&b| b == b'\n').count();
+            for kernel in fast::kernels() {
+                assert_eq!(fast::count_newlines(kernel, &text), expected);
+            }
+        }
+    }
 }
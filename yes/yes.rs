@@ -1,25 +1,36 @@
-use std::os;
+use common::Command;
+use std::io;
+use std::io::Write;
 
-fn main() {
-    let args = os::args();
-    let len = args.len();
-    let default_yes = String::from_str("y");
+static PROGRAM: &str = "yes";
 
-    let yes = match len {
-        1 => default_yes,
-        _ => args.slice(1, len).connect(" ")
-    };
+pub fn command() -> Command {
+    Command::new(PROGRAM,
+        &["[STRING]...", "OPTION"],
+        "Repeatedly output a line with all specified STRING(s), or 'y'.",
+        "",
+        &[])
+}
 
-    if yes.as_slice().char_at(0) == '-' {
-        if yes == String::from_str("--help") {
-            println!("Help");
-        } else {
-            println!("Unrecognized option: {}", yes);
-        }
+pub fn uumain(args: Vec<String>) {
+    let mut words = &args[1..];
 
-    } else {
-        loop {
-            println!("{}", yes);
+    // Only a leading option is parsed: `yes a -b` repeats "a -b"
+    if !words.is_empty() && words[0].len() > 1 && words[0].starts_with('-') {
+        match command().parse(&words[..1]) {
+            // The argument was "--"
+            Some(_) => words = &words[1..],
+            None => return
         }
     }
+
+    let yes = if words.is_empty() {
+        String::from("y")
+    } else {
+        words.join(" ")
+    };
+
+    // Stop quietly once the reader goes away
+    let mut out = io::stdout().lock();
+    while writeln!(out, "{}", yes).is_ok() {}
 }